@@ -1,22 +1,25 @@
-use std::process;
+use super::error::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Algorithm {
     AStar,
     BStar,
     Greedy,
+    IdaStar,
+    Bidirectional,
+    AraStar,
 }
 
 impl Algorithm {
-    pub fn parse(s: String) -> Algorithm {
-        match &s.to_lowercase() as &str {
+    pub fn parse(s: String) -> Result<Algorithm, NPuzzleError> {
+        Ok(match &s.to_lowercase() as &str {
             "astar" => Algorithm::AStar,
             "bstar" => Algorithm::BStar,
             "greedy" => Algorithm::Greedy,
-            _ => {
-                println!("Algorithm not recognized");
-                process::exit(1);
-            }
-        }
+            "idastar" | "ida" => Algorithm::IdaStar,
+            "bidirectional" | "bidir" | "mm" => Algorithm::Bidirectional,
+            "arastar" | "ara" => Algorithm::AraStar,
+            _ => return Err(NPuzzleError::UnknownAlgorithm(s)),
+        })
     }
 }
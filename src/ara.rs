@@ -0,0 +1,175 @@
+use super::board::*;
+use super::node::*;
+use super::solution::*;
+use super::{NPuzzle, Outcome, Stop};
+use std::cmp;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::f64;
+use std::sync::Arc;
+
+/*
+ * Weight used by ARA* when no weight greater than 1 was given,
+ * and how much it is lowered after each published solution
+ */
+const INITIAL_WEIGHT: f64 = 3.0;
+const WEIGHT_STEP: f64 = 0.5;
+
+impl NPuzzle {
+    /*
+     * Anytime Repairing A*: a weighted A* which publishes a first path
+     * quickly, then lowers the weight and repairs the previous search
+     * (reusing the inconsistent nodes) to publish better and better paths,
+     * until the solution is proven optimal or the budget runs out
+     */
+    pub(crate) fn run_arastar(&mut self) -> Outcome {
+        let root = self.open_list.pop().unwrap();
+        let mut weight = if self.weight > 1.0 {
+            self.weight
+        } else {
+            INITIAL_WEIGHT
+        };
+        let mut epochs: u64 = 0;
+        let mut best: HashMap<Board, Arc<Node>> = HashMap::new();
+        let mut open: BinaryHeap<Candidate> = BinaryHeap::new();
+        let mut incons: Vec<Arc<Node>> = vec![];
+        best.insert(root.grid.clone(), root.clone());
+        open.push(Candidate::new(root.g + weight * root.h, root));
+
+        let outcome = loop {
+            let mut closed: HashSet<Board> = HashSet::new();
+            if let Err(stop) = self.improve_path(
+                weight,
+                &mut open,
+                &mut best,
+                &mut closed,
+                &mut incons,
+                &mut epochs,
+            ) {
+                // Keep the last published path if there is one
+                break match best.get(&self.goal) {
+                    Some(solved) => Outcome::Stopped(solved.clone(), stop),
+                    None => {
+                        let closest = best
+                            .values()
+                            .min_by(|a, b| a.h.partial_cmp(&b.h).unwrap())
+                            .unwrap();
+                        Outcome::GaveUp(closest.clone(), stop)
+                    }
+                };
+            }
+            let solved = best[&self.goal].clone();
+
+            // The lowest unweighted f left to explore bounds the optimal cost
+            let lower = open
+                .iter()
+                .map(|c| &c.node)
+                .chain(incons.iter())
+                .filter(|n| best[&n.grid].g >= n.g)
+                .map(|n| n.g + n.h)
+                .fold(f64::INFINITY, f64::min);
+            let bound = if lower >= solved.g {
+                1.0
+            } else {
+                weight.min(solved.g / lower)
+            };
+            let improvement = Improvement {
+                weight,
+                moves: solved.g,
+                bound,
+            };
+            if let Some(on_improvement) = self.on_improvement {
+                on_improvement(self.stats.improvements.len() + 1, &improvement);
+            }
+            self.stats.improvements.push(improvement);
+            if bound <= 1.0 {
+                break Outcome::Solved(solved);
+            }
+
+            weight = (weight - WEIGHT_STEP).max(1.0);
+            let pending: Vec<Arc<Node>> = open
+                .drain()
+                .map(|c| c.node)
+                .chain(incons.drain(..))
+                .filter(|n| best[&n.grid].g >= n.g)
+                .collect();
+            open = pending
+                .into_iter()
+                .map(|n| Candidate::new(n.g + weight * n.h, n))
+                .collect();
+        };
+        self.stats.iterations = epochs;
+        outcome
+    }
+
+    /*
+     * Expand nodes by increasing g + weight * h until the goal cannot be
+     * reached more cheaply; nodes improved after being closed are kept
+     * aside for the next, less greedy, search
+     */
+    fn improve_path(
+        &mut self,
+        weight: f64,
+        open: &mut BinaryHeap<Candidate>,
+        best: &mut HashMap<Board, Arc<Node>>,
+        closed: &mut HashSet<Board>,
+        incons: &mut Vec<Arc<Node>>,
+        epochs: &mut u64,
+    ) -> Result<(), Stop> {
+        while let Some(top) = open.pop() {
+            let current = top.node;
+            if best[&current.grid].g < current.g {
+                continue;
+            }
+            if let Some(goal) = best.get(&self.goal) {
+                if goal.g <= top.priority {
+                    open.push(Candidate::new(top.priority, current));
+                    return Ok(());
+                }
+            }
+            *epochs += 1;
+            if let Some(stop) = self.stop(*epochs) {
+                open.push(Candidate::new(top.priority, current));
+                return Err(stop);
+            }
+            self.trace(|| format!("EPOCH: {}\nCURRENT : {:?}", epochs, current));
+            closed.insert(current.grid.clone());
+
+            for child in self.expand(&current) {
+                if let Some(known) = best.get(&child.grid) {
+                    if known.g <= child.g {
+                        continue;
+                    }
+                }
+                best.insert(child.grid.clone(), child.clone());
+                if closed.contains(&child.grid) {
+                    incons.push(child);
+                } else {
+                    open.push(Candidate::new(child.g + weight * child.h, child));
+                }
+            }
+            self.stats.max_states = cmp::max(self.stats.max_states, open.len() + incons.len());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::heuristique::*;
+    use crate::tests::*;
+    #[test]
+    fn test_arastar_bounds() {
+        for (file, goal) in SAMPLES.iter() {
+            let optimal = solve(file, "astar", Heuristique::Manhattan, goal, 1.0).cost;
+            let solution = solve(file, "arastar", Heuristique::Manhattan, goal, 1.0);
+            let improvements = &solution.stats.improvements;
+            for pair in improvements.windows(2) {
+                assert!(pair[1].bound <= pair[0].bound, "{:?}", improvements);
+                assert!(pair[1].moves <= pair[0].moves, "{:?}", improvements);
+            }
+            let last = improvements.last().unwrap();
+            assert_eq!((optimal, 1.0), (last.moves, last.bound));
+            assert_eq!(optimal, solution.cost);
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use super::error::*;
+use super::file::*;
+use super::goal::*;
+use super::solution::*;
+use super::{NPuzzle, Stop};
+use rayon::prelude::*;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/*
+ * What became of one puzzle of a batch
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Solved,
+    Unsolvable,
+    ParseError,
+    Timeout,
+    GaveUp,
+    Error,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Status::Solved => write!(f, "solved"),
+            Status::Unsolvable => write!(f, "unsolvable"),
+            Status::ParseError => write!(f, "parse error"),
+            Status::Timeout => write!(f, "timeout"),
+            Status::GaveUp => write!(f, "gave up"),
+            Status::Error => write!(f, "error"),
+        }
+    }
+}
+
+/*
+ * A puzzle to solve: its name, its text (or why it cannot be read), and
+ * the goal to reach
+ */
+pub struct Job {
+    pub name: String,
+    pub source: Result<String, NPuzzleError>,
+    pub goal: Goal,
+}
+
+pub struct Entry {
+    pub name: String,
+    pub status: Status,
+    pub solution: Option<Solution>,
+    pub error: Option<NPuzzleError>,
+}
+
+impl Entry {
+    pub fn new(name: String, solution: Result<Solution, NPuzzleError>) -> Entry {
+        match solution {
+            Ok(solution) => Entry {
+                name,
+                status: match solution.stop {
+                    _ if solution.solved => Status::Solved,
+                    Some(Stop::Timeout) => Status::Timeout,
+                    _ => Status::GaveUp,
+                },
+                solution: Some(solution),
+                error: None,
+            },
+            Err(err) => Entry {
+                name,
+                status: match err {
+                    NPuzzleError::Unsolvable(_) => Status::Unsolvable,
+                    NPuzzleError::Parse(_) | NPuzzleError::GoalParse(_, _) => Status::ParseError,
+                    _ => Status::Error,
+                },
+                solution: None,
+                error: Some(err),
+            },
+        }
+    }
+}
+
+/*
+ * Every puzzle of a directory, one per file (sorted by name, hidden files
+ * left out), or of a multi-puzzle file.
+ * With a goal directory, the goal of a puzzle is the file of that directory
+ * which has the same name.
+ */
+pub fn jobs(path: &Path, goals: Option<&Path>, goal: &Goal) -> Result<Vec<Job>, NPuzzleError> {
+    let dir = path.is_dir();
+    let files = if dir {
+        let mut files: Vec<_> = fs::read_dir(path)
+            .map_err(|e| NPuzzleError::Read(path.display().to_string(), e))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            .filter(|path| {
+                path.file_name()
+                    .is_some_and(|name| !name.to_string_lossy().starts_with('.'))
+            })
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+    let mut res = vec![];
+    for file in files {
+        let goal = match (goals, file.file_name()) {
+            (Some(dir), Some(name)) => Goal::Custom(dir.join(name).display().to_string()),
+            _ => goal.clone(),
+        };
+        let puzzles = match fs::read_to_string(&file) {
+            Ok(text) if dir => vec![(1, text)],
+            Ok(text) => split_puzzles(&text),
+            Err(e) => {
+                res.push(Job {
+                    name: file.display().to_string(),
+                    source: Err(NPuzzleError::Read(file.display().to_string(), e)),
+                    goal,
+                });
+                continue;
+            }
+        };
+        let several = puzzles.len() > 1;
+        for (line, source) in puzzles {
+            let name = match several {
+                true => format!("{}:{}", file.display(), line),
+                false => file.display().to_string(),
+            };
+            res.push(Job {
+                name,
+                source: Ok(source),
+                goal: goal.clone(),
+            });
+        }
+    }
+    Ok(res)
+}
+
+/*
+ * Solve every job in parallel, new building the puzzle from its tiles
+ */
+pub fn solve<F>(jobs: Vec<Job>, new: F) -> Vec<Entry>
+where
+    F: Fn(ParsedMap, Goal) -> Result<NPuzzle, NPuzzleError> + Sync,
+{
+    jobs.into_par_iter()
+        .map(|Job { name, source, goal }| {
+            let solution = source
+                .and_then(|source| parse_file(source).map_err(NPuzzleError::from))
+                .and_then(|map| new(map, goal))
+                .and_then(|mut puzzle| puzzle.run());
+            Entry::new(name, solution)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::*;
+    use crate::heuristique::*;
+    use std::env;
+    use std::time::Duration;
+
+    /*
+     * A fresh directory holding the given files
+     */
+    fn dir(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = env::temp_dir().join(format!("npuzzle-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (file, text) in files {
+            fs::write(dir.join(file), text).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_jobs_directory() {
+        let puzzles = dir(
+            "jobs",
+            &[
+                ("b.txt", "2\n1 2\n3 0\n"),
+                ("a.txt", "2\n1 2\n0 3\n"),
+                (".hidden", "2\n1 2\n3 0\n"),
+            ],
+        );
+        let found = jobs(&puzzles, None, &Goal::Standard).unwrap();
+        let names: Vec<String> = found.iter().map(|job| job.name.clone()).collect();
+        let at = |file| puzzles.join(file).display().to_string();
+        assert_eq!(vec![at("a.txt"), at("b.txt")], names);
+        assert_eq!("2\n1 2\n0 3\n", found[0].source.as_ref().unwrap());
+        assert!(found.iter().all(|job| matches!(job.goal, Goal::Standard)));
+        // Goals are looked up by file name
+        let goals = Path::new("goals");
+        let found = jobs(&puzzles, Some(goals), &Goal::Standard).unwrap();
+        let goal = goals.join("b.txt").display().to_string();
+        assert!(matches!(&found[1].goal, Goal::Custom(path) if path == &goal));
+        fs::remove_dir_all(puzzles).unwrap();
+    }
+    #[test]
+    fn test_jobs_file() {
+        let text = "2\n1 2\n3 0\n\n# second\n2\n1 2\n0 3\n";
+        let puzzles = dir("file", &[("all.txt", text)]);
+        let file = puzzles.join("all.txt");
+        let found = jobs(&file, None, &Goal::Snail).unwrap();
+        let names: Vec<String> = found.iter().map(|job| job.name.clone()).collect();
+        let at = |line| format!("{}:{}", file.display(), line);
+        assert_eq!(vec![at(1), at(6)], names);
+        assert!(found.iter().all(|job| matches!(job.goal, Goal::Snail)));
+        let missing = jobs(&puzzles.join("none.txt"), None, &Goal::Snail).unwrap();
+        assert!(matches!(missing[0].source, Err(NPuzzleError::Read(_, _))));
+        fs::remove_dir_all(puzzles).unwrap();
+    }
+    #[test]
+    fn test_solve_status() {
+        let job = |name: &str, source: &str| Job {
+            name: name.to_string(),
+            source: Ok(source.to_string()),
+            goal: Goal::Standard,
+        };
+        let hard = "4\n0 12 9 13\n15 11 10 14\n3 7 2 5\n4 8 6 1\n";
+        let jobs = vec![
+            job("solved", "2\n1 2\n0 3\n"),
+            job("unsolvable", "2\n2 1\n3 0\n"),
+            job("parse error", "2\n1 2\n3\n"),
+            job("timeout", hard),
+            Job {
+                name: "unreadable".to_string(),
+                source: Err(NPuzzleError::BoardTooLarge),
+                goal: Goal::Standard,
+            },
+        ];
+        let entries = solve(jobs, |map, goal| {
+            NPuzzle::new(
+                map,
+                Heuristique::Manhattan,
+                Algorithm::AStar,
+                "std".to_string(),
+                goal,
+                1.0,
+                u64::MAX,
+                Some(Duration::from_millis(20)),
+                false,
+                None,
+                1,
+            )
+        });
+        let statuses: Vec<(&str, Status)> = entries
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.status))
+            .collect();
+        assert_eq!(
+            vec![
+                ("solved", Status::Solved),
+                ("unsolvable", Status::Unsolvable),
+                ("parse error", Status::ParseError),
+                ("timeout", Status::Timeout),
+                ("unreadable", Status::Error),
+            ],
+            statuses
+        );
+        assert!(entries[0].solution.is_some() && entries[0].error.is_none());
+        assert!(entries[1].solution.is_none() && entries[1].error.is_some());
+        // Out of iterations
+        let entries = solve(vec![job("gave up", hard)], |map, goal| {
+            NPuzzle::new(
+                map,
+                Heuristique::Manhattan,
+                Algorithm::AStar,
+                "std".to_string(),
+                goal,
+                1.0,
+                1,
+                None,
+                false,
+                None,
+                1,
+            )
+        });
+        assert_eq!(Status::GaveUp, entries[0].status);
+    }
+}
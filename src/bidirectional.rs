@@ -0,0 +1,195 @@
+use super::board::*;
+use super::error::*;
+use super::node::*;
+use super::strategy::*;
+use super::{NPuzzle, Outcome};
+use std::cmp;
+use std::collections::{BinaryHeap, HashMap};
+use std::f64;
+use std::sync::Arc;
+
+/*
+ * Open lists are ordered by the MM priority max(f, 2g)
+ */
+fn mm_priority(node: &Node) -> f64 {
+    node.f.max(2.0 * node.g)
+}
+
+/*
+ * One direction of the search: its open list, the cheapest node reached
+ * for every grid, and the homes of the target the heuristic is measured
+ * against
+ */
+struct Frontier {
+    open: BinaryHeap<Candidate>,
+    reached: HashMap<Board, Arc<Node>>,
+    homes: Homes,
+    strategy: Strategy,
+}
+
+impl Frontier {
+    fn new(root: Arc<Node>, target: Board, strategy: Strategy) -> Frontier {
+        let mut reached = HashMap::new();
+        reached.insert(root.grid.clone(), root.clone());
+        let mut open = BinaryHeap::new();
+        open.push(Candidate::new(mm_priority(&root), root));
+        Frontier {
+            open,
+            reached,
+            homes: Homes::new(&target),
+            strategy,
+        }
+    }
+
+    /*
+     * Lowest priority still open, dropping entries superseded by a cheaper path
+     */
+    fn min_priority(&mut self) -> f64 {
+        while let Some(top) = self.open.peek() {
+            if self.reached[&top.node.grid].g < top.node.g {
+                self.open.pop();
+            } else {
+                return top.priority;
+            }
+        }
+        f64::INFINITY
+    }
+}
+
+impl NPuzzle {
+    /*
+     * Bidirectional MM search: a forward search from the initial grid and a
+     * backward one from the goal, always expanding the side with the lowest
+     * priority max(f, 2g). Once the best known meeting cost U is lower or
+     * equal to that priority no shorter path can exist.
+     */
+    pub(crate) fn run_bidirectional(&mut self) -> Result<Outcome, NPuzzleError> {
+        let root = self.open_list.pop().unwrap();
+        let initial = root.grid.clone();
+        // A table keyed on every initial grid would be built, and cached on
+        // disk, for each puzzle solved
+        let mut backward_strategy = self.strategy.without_table();
+        backward_strategy.init(&initial)?;
+        let goal_node = Arc::new(Node::new(
+            self.goal.clone(),
+            None,
+            &initial,
+            &self.algorithm,
+            &backward_strategy,
+            self.weight,
+        ));
+        let mut forward = Frontier::new(root.clone(), self.goal.clone(), self.strategy.clone());
+        let mut backward = Frontier::new(goal_node, initial, backward_strategy);
+
+        let mut epochs: u64 = 0;
+        let mut best = f64::INFINITY;
+        let mut meeting: Option<(Arc<Node>, Arc<Node>)> = None;
+        let mut closest = root.clone();
+        if root.grid == self.goal {
+            best = 0.0;
+            meeting = Some((root.clone(), backward.reached[&self.goal].clone()));
+        }
+
+        loop {
+            let c = forward.min_priority().min(backward.min_priority());
+            if best <= c {
+                break;
+            }
+            epochs += 1;
+            if let Some(stop) = self.stop(epochs) {
+                self.stats.iterations = epochs;
+                return Ok(Outcome::GaveUp(closest, stop));
+            }
+            let is_forward = forward.min_priority() <= backward.min_priority();
+            let (current, other) = if is_forward {
+                (&mut forward, &backward)
+            } else {
+                (&mut backward, &forward)
+            };
+            let node = current.open.pop().unwrap().node;
+            // Only forward nodes measure their distance to the goal
+            if is_forward && node.h < closest.h {
+                closest = node.clone();
+            }
+
+            self.trace(|| {
+                format!(
+                    "EPOCH: {}\n{} : {:?}",
+                    epochs,
+                    if is_forward { "FORWARD" } else { "BACKWARD" },
+                    node
+                )
+            });
+
+            for child in self.expand_towards(&node, &current.homes, &current.strategy) {
+                if let Some(known) = current.reached.get(&child.grid) {
+                    if known.g <= child.g {
+                        continue;
+                    }
+                }
+                if let Some(opposite) = other.reached.get(&child.grid) {
+                    if child.g + opposite.g < best {
+                        best = child.g + opposite.g;
+                        meeting = Some(if is_forward {
+                            (child.clone(), opposite.clone())
+                        } else {
+                            (opposite.clone(), child.clone())
+                        });
+                    }
+                }
+                current.reached.insert(child.grid.clone(), child.clone());
+                current
+                    .open
+                    .push(Candidate::new(mm_priority(&child), child));
+            }
+            self.stats.max_states = cmp::max(
+                self.stats.max_states,
+                forward.open.len() + backward.open.len(),
+            );
+        }
+
+        self.stats.iterations = epochs;
+        let (front, back) = meeting.unwrap();
+        Ok(Outcome::Solved(self.stitch(front, &back)))
+    }
+
+    /*
+     * Continue the forward half-path with the backward one, walked from
+     * the meeting grid up to the goal
+     */
+    fn stitch(&self, front: Arc<Node>, back: &Arc<Node>) -> Arc<Node> {
+        let mut path = front;
+        let mut cur = back.parent.clone();
+        while let Some(node) = cur {
+            path = Arc::new(Node::new(
+                node.grid.clone(),
+                Some(path),
+                &self.goal,
+                &self.algorithm,
+                &self.strategy,
+                self.weight,
+            ));
+            cur = node.parent.clone();
+        }
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::heuristique::*;
+    use crate::tests::*;
+    #[test]
+    fn test_bidirectional_optimal() {
+        for (file, goal) in SAMPLES.iter() {
+            for heuristique in [
+                Heuristique::Manhattan,
+                Heuristique::LinearConflict,
+                Heuristique::Perfect(None),
+            ] {
+                let cost = |algorithm| solve(file, algorithm, heuristique.clone(), goal, 1.0).cost;
+                assert_eq!(cost("astar"), cost("mm"), "{}", file);
+            }
+        }
+    }
+}
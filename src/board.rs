@@ -0,0 +1,233 @@
+use std::fmt;
+
+/*
+ * Tiles in row-major order: one nibble per cell when the board has at most
+ * 16 cells numbered below 16, one byte per cell otherwise
+ */
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Cells {
+    Packed(u64),
+    Bytes(Box<[u8]>),
+}
+
+/*
+ * Compact board, remembering where the empty space is
+ */
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Board {
+    rows: u8,
+    cols: u8,
+    blank: u8,
+    cells: Cells,
+}
+
+impl Board {
+    /*
+     * None when the grid is not rectangular, has more than 256 cells, has no
+     * empty space or holds a tile which does not fit in a byte
+     */
+    pub fn new(grid: &[Vec<i64>]) -> Option<Board> {
+        let rows = grid.len();
+        let cols = grid.first().map_or(0, |row| row.len());
+        if rows * cols == 0 || rows * cols > 256 || grid.iter().any(|row| row.len() != cols) {
+            return None;
+        }
+        let tiles: Vec<i64> = grid.iter().flatten().cloned().collect();
+        if tiles.iter().any(|&t| !(0..=u8::MAX as i64).contains(&t)) {
+            return None;
+        }
+        let blank = tiles.iter().position(|&t| t == 0)? as u8;
+        let cells = if tiles.len() <= 16 && tiles.iter().all(|&t| t < 16) {
+            Cells::Packed(
+                tiles
+                    .iter()
+                    .enumerate()
+                    .fold(0, |packed, (c, &t)| packed | (t as u64) << (4 * c)),
+            )
+        } else {
+            Cells::Bytes(tiles.iter().map(|&t| t as u8).collect())
+        };
+        Some(Board {
+            rows: rows as u8,
+            cols: cols as u8,
+            blank,
+            cells,
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows as usize
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols as usize
+    }
+
+    /*
+     * Number of cells
+     */
+    pub fn len(&self) -> usize {
+        self.rows() * self.cols()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /*
+     * Tile on the cell, numbered in row-major order
+     */
+    pub fn get(&self, cell: usize) -> i64 {
+        match &self.cells {
+            Cells::Packed(packed) => (packed >> (4 * cell) & 0xf) as i64,
+            Cells::Bytes(bytes) => bytes[cell] as i64,
+        }
+    }
+
+    pub fn at(&self, (i, j): (usize, usize)) -> i64 {
+        self.get(i * self.cols() + j)
+    }
+
+    pub fn blank(&self) -> (usize, usize) {
+        (
+            self.blank as usize / self.cols(),
+            self.blank as usize % self.cols(),
+        )
+    }
+
+    pub fn tiles(&self) -> impl Iterator<Item = i64> + '_ {
+        (0..self.len()).map(move |c| self.get(c))
+    }
+
+    /*
+     * Coordinates of the tile, if it is on the board
+     */
+    pub fn position(&self, tile: i64) -> Option<(usize, usize)> {
+        self.tiles()
+            .position(|t| t == tile)
+            .map(|c| (c / self.cols(), c % self.cols()))
+    }
+
+    /*
+     * Board obtained by sliding the tile at (i, j) into the empty space
+     */
+    pub fn slide(&self, (i, j): (usize, usize)) -> Board {
+        let from = i * self.cols() + j;
+        let to = self.blank as usize;
+        let cells = match &self.cells {
+            Cells::Packed(packed) => {
+                let tile = packed >> (4 * from) & 0xf;
+                Cells::Packed(packed & !(0xf << (4 * from)) | tile << (4 * to))
+            }
+            Cells::Bytes(bytes) => {
+                let mut bytes = bytes.clone();
+                bytes.swap(from, to);
+                Cells::Bytes(bytes)
+            }
+        };
+        Board {
+            rows: self.rows,
+            cols: self.cols,
+            blank: from as u8,
+            cells,
+        }
+    }
+
+    pub fn grid(&self) -> Vec<Vec<i64>> {
+        (0..self.rows())
+            .map(|i| (0..self.cols()).map(|j| self.at((i, j))).collect())
+            .collect()
+    }
+}
+
+/*
+ * Cell of every tile of a board, indexed by tile: built once for a goal so
+ * that heuristiques find where a tile belongs without scanning it
+ */
+#[derive(Debug, Clone)]
+pub struct Homes(Vec<Option<(usize, usize)>>);
+
+impl Homes {
+    pub fn new(board: &Board) -> Homes {
+        let mut homes = vec![None; board.tiles().max().map_or(0, |t| t as usize + 1)];
+        for (c, tile) in board.tiles().enumerate() {
+            homes[tile as usize] = Some((c / board.cols(), c % board.cols()));
+        }
+        Homes(homes)
+    }
+
+    pub fn get(&self, tile: i64) -> Option<(usize, usize)> {
+        self.0.get(tile as usize).copied().flatten()
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.grid()
+                .iter()
+                .map(|x| x
+                    .iter()
+                    .map(|&y| y.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" "))
+                .collect::<Vec<String>>()
+                .join("\n"),
+        )
+    }
+}
+
+impl fmt::Debug for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.grid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_board_packed() {
+        let grid = vec![vec![1, 2, 3], vec![8, 0, 4], vec![7, 6, 5]];
+        let board = Board::new(&grid).unwrap();
+        assert!(matches!(board.cells, Cells::Packed(_)));
+        assert_eq!(grid, board.grid());
+        assert_eq!((1, 1), board.blank());
+        assert_eq!(Some((2, 0)), board.position(7));
+        assert_eq!(Some((2, 0)), Homes::new(&board).get(7));
+        assert_eq!(None, Homes::new(&board).get(9));
+        let moved = board.slide((0, 1));
+        assert_eq!(
+            vec![vec![1, 0, 3], vec![8, 2, 4], vec![7, 6, 5]],
+            moved.grid()
+        );
+        assert_eq!((0, 1), moved.blank());
+        assert_eq!(board, moved.slide((1, 1)));
+    }
+    #[test]
+    fn test_board_rectangle() {
+        let grid = vec![vec![1, 2, 3, 4], vec![5, 6, 7, 0]];
+        let board = Board::new(&grid).unwrap();
+        assert_eq!((2, 4), (board.rows(), board.cols()));
+        assert_eq!((1, 3), board.blank());
+        assert_eq!(
+            vec![vec![1, 2, 3, 0], vec![5, 6, 7, 4]],
+            board.slide((0, 3)).grid()
+        );
+    }
+    #[test]
+    fn test_board_bytes() {
+        let grid: Vec<Vec<i64>> = (0..5)
+            .map(|i| (0..5).map(|j| i * 5 + j).collect())
+            .collect();
+        let board = Board::new(&grid).unwrap();
+        assert!(matches!(board.cells, Cells::Bytes(_)));
+        assert_eq!(grid, board.grid());
+        assert_eq!(5, board.slide((1, 0)).get(0));
+        assert!(Board::new(&[vec![1, 2], vec![3, 4]]).is_none());
+        assert!(Board::new(&[vec![1, 2], vec![0]]).is_none());
+        assert!(Board::new(&[vec![0, 300], vec![1, 2]]).is_none());
+    }
+}
@@ -0,0 +1,269 @@
+use super::error::*;
+use std::collections::HashMap;
+
+/*
+ * An option of a command: value names the value it takes, None for a flag
+ */
+pub struct Opt {
+    pub long: &'static str,
+    pub short: Option<char>,
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+}
+
+/*
+ * A subcommand, its positional arguments ("<NAME>" required, "[NAME]"
+ * optional) and its options, listed by group in the help
+ */
+pub struct Command {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub args: &'static [&'static str],
+    pub about: &'static str,
+    pub groups: &'static [(&'static str, &'static [Opt])],
+}
+
+impl Command {
+    fn options(&self) -> impl Iterator<Item = &Opt> {
+        self.groups.iter().flat_map(|(_, options)| options.iter())
+    }
+
+    /*
+     * Options can be given as --long value, --long=value or -s value,
+     * anything after -- is positional. --help (-h) wins over everything else.
+     */
+    pub fn parse(&self, args: Vec<String>) -> Result<Matches, NPuzzleError> {
+        let mut res = Matches::default();
+        if args
+            .iter()
+            .take_while(|&a| a != "--")
+            .any(|a| a == "--help" || a == "-h")
+        {
+            res.help = true;
+            return Ok(res);
+        }
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                res.args.extend(args.by_ref());
+                break;
+            }
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) if arg.starts_with("--") => (name, Some(value.to_string())),
+                _ => (&arg as &str, None),
+            };
+            let opt = match name.strip_prefix("--") {
+                Some(long) => self.options().find(|o| o.long == long),
+                None if name.len() > 1 && name.starts_with('-') => {
+                    let mut short = name[1..].chars();
+                    match (short.next(), short.next()) {
+                        (c, None) => self.options().find(|o| o.short == c),
+                        _ => None,
+                    }
+                }
+                // A lone - is stdin
+                None => {
+                    res.args.push(arg);
+                    continue;
+                }
+            };
+            let opt = opt.ok_or_else(|| {
+                NPuzzleError::Usage(format!("unknown option {} for {}", name, self.name))
+            })?;
+            let value = match (opt.value, inline) {
+                (None, None) => String::new(),
+                (None, Some(_)) => {
+                    return Err(NPuzzleError::Usage(format!(
+                        "--{} does not take a value",
+                        opt.long
+                    )))
+                }
+                (Some(_), Some(value)) => value,
+                (Some(value), None) => args.next().ok_or_else(|| {
+                    NPuzzleError::Usage(format!("--{} needs a value {}", opt.long, value))
+                })?,
+            };
+            res.values.insert(opt.long, value);
+        }
+        let required = self.args.iter().filter(|a| a.starts_with('<')).count();
+        if res.args.len() < required {
+            return Err(NPuzzleError::Usage(format!(
+                "{} expects {}",
+                self.name,
+                self.args.join(" ")
+            )));
+        }
+        if res.args.len() > self.args.len() {
+            return Err(NPuzzleError::Usage(format!(
+                "unexpected argument {} for {}",
+                res.args[self.args.len()],
+                self.name
+            )));
+        }
+        Ok(res)
+    }
+
+    pub fn help(&self, program: &str) -> String {
+        let mut res = format!(
+            "{}\n\nUsage: {} {} [OPTIONS] {}\n",
+            self.about,
+            program,
+            self.name,
+            self.args.join(" ")
+        );
+        if !self.aliases.is_empty() {
+            res += &format!("Alias: {}\n", self.aliases.join(", "));
+        }
+        let flags = |o: &Opt| {
+            let mut flags = match o.short {
+                Some(c) => format!("-{}, --{}", c, o.long),
+                None => format!("    --{}", o.long),
+            };
+            if let Some(value) = o.value {
+                flags = flags + " " + value;
+            }
+            flags
+        };
+        let width = self.options().map(|o| flags(o).len()).max().unwrap_or(0);
+        for (title, options) in self.groups.iter() {
+            res += &format!("\n{}:\n", title);
+            for o in options.iter() {
+                res += &format!("  {:<width$}  {}\n", flags(o), o.help, width = width);
+            }
+        }
+        res + &format!(
+            "\n  {:<width$}  Print this help\n",
+            "-h, --help",
+            width = width
+        )
+    }
+}
+
+/*
+ * The program help, listing every command
+ */
+pub fn help(program: &str, about: &str, commands: &[&Command]) -> String {
+    let width = commands.iter().map(|c| c.name.len()).max().unwrap_or(0);
+    let mut res = format!(
+        "{}\n\nUsage: {} <COMMAND> [OPTIONS]\n\nCommands:\n",
+        about, program
+    );
+    for command in commands {
+        res += &format!(
+            "  {:<width$}  {}\n",
+            command.name,
+            command.about,
+            width = width
+        );
+    }
+    res + &format!(
+        "\nRun '{} <COMMAND> --help' for the options of a command.\n",
+        program
+    )
+}
+
+/*
+ * The command called name, or one of its aliases
+ */
+pub fn find<'a>(commands: &[&'a Command], name: &str) -> Option<&'a Command> {
+    commands
+        .iter()
+        .find(|c| c.name == name || c.aliases.contains(&name))
+        .copied()
+}
+
+/*
+ * What was given on the command line, by long option name
+ */
+#[derive(Debug, Default)]
+pub struct Matches {
+    pub help: bool,
+    pub args: Vec<String>,
+    values: HashMap<&'static str, String>,
+}
+
+impl Matches {
+    pub fn flag(&self, long: &str) -> bool {
+        self.values.contains_key(long)
+    }
+
+    pub fn value(&self, long: &str) -> Option<&str> {
+        self.values.get(long).map(|v| v as &str)
+    }
+
+    /*
+     * The value read by parse, None when the option was not given
+     */
+    pub fn parse<T>(
+        &self,
+        long: &str,
+        parse: impl FnOnce(String) -> Result<T, NPuzzleError>,
+    ) -> Result<Option<T>, NPuzzleError> {
+        self.value(long).map(|v| parse(v.to_string())).transpose()
+    }
+
+    /*
+     * A number accepted by valid, explained by expected otherwise
+     */
+    pub fn number<T: std::str::FromStr>(
+        &self,
+        long: &str,
+        valid: impl Fn(&T) -> bool,
+        expected: &str,
+    ) -> Result<Option<T>, NPuzzleError> {
+        self.parse(long, |v| match v.parse::<T>() {
+            Ok(n) if valid(&n) => Ok(n),
+            _ => Err(NPuzzleError::Usage(format!(
+                "--{} has to be {}, not {}",
+                long, expected, v
+            ))),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const COMMAND: Command = Command {
+        name: "solve",
+        aliases: &[],
+        args: &["<PUZZLE>", "[MOVES]"],
+        about: "Solve a puzzle",
+        groups: &[(
+            "Options",
+            &[
+                Opt {
+                    long: "weight",
+                    short: Some('w'),
+                    value: Some("<W>"),
+                    help: "Weight of h",
+                },
+                Opt {
+                    long: "fix",
+                    short: None,
+                    value: None,
+                    help: "Swap two tiles",
+                },
+            ],
+        )],
+    };
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(|a| a.to_string()).collect()
+    }
+    #[test]
+    fn test_parse_command() {
+        let m = COMMAND.parse(args("-w 2 a.txt --fix")).unwrap();
+        assert_eq!(vec!["a.txt"], m.args);
+        assert!(m.flag("fix"));
+        assert_eq!(Some(2.0), m.number("weight", |&w| w >= 1.0, "").unwrap());
+        let m = COMMAND.parse(args("--weight=0.5 -")).unwrap();
+        assert_eq!(vec!["-"], m.args);
+        assert!(m.number::<f64>("weight", |&w| w >= 1.0, "").is_err());
+        assert!(COMMAND.parse(args("a.txt --weight")).is_err());
+        assert!(COMMAND.parse(args("a.txt --verbose")).is_err());
+        assert!(COMMAND.parse(args("a b c")).is_err());
+        assert!(COMMAND.parse(args("")).is_err());
+        assert!(COMMAND.parse(args("--verbose -h")).unwrap().help);
+        assert!(COMMAND.help("npuzzle").contains("-w, --weight <W>"));
+    }
+}
@@ -0,0 +1,98 @@
+use super::algorithm::*;
+use super::error::*;
+use super::file::*;
+use super::goal::*;
+use super::heuristique::*;
+use super::solution::*;
+use super::NPuzzle;
+use std::thread;
+use std::time::Duration;
+
+/*
+ * How to search, whatever the puzzle: every command that solves builds its
+ * puzzles from one of these
+ */
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub heuristique: Heuristique,
+    pub algorithm: Algorithm,
+    pub strategy: String,
+    pub goal: Goal,
+    pub weight: f64,
+    pub max_iteration: u64,
+    pub timeout: Option<Duration>,
+    /* Swap two tiles of an unsolvable puzzle */
+    pub fix: bool,
+    pub trace: Option<fn(&str)>,
+    /* Receives every path ARA* publishes, numbered from 1 */
+    pub on_improvement: Option<fn(usize, &Improvement)>,
+    /* Nodes expanded together at each iteration, at least 1 */
+    pub thread: usize,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            heuristique: Heuristique::Manhattan,
+            algorithm: Algorithm::AStar,
+            strategy: "std".to_string(),
+            goal: Goal::Snail,
+            weight: 1.0,
+            max_iteration: 10_000_000,
+            timeout: None,
+            fix: false,
+            trace: None,
+            on_improvement: None,
+            thread: 1,
+        }
+    }
+}
+
+impl Config {
+    /*
+     * The puzzle ready to run, towards goal rather than the configured one
+     * when given
+     */
+    pub fn puzzle(&self, map: ParsedMap, goal: Option<Goal>) -> Result<NPuzzle, NPuzzleError> {
+        let mut puzzle = NPuzzle::new(
+            map,
+            self.heuristique.clone(),
+            self.algorithm,
+            self.strategy.clone(),
+            goal.unwrap_or_else(|| self.goal.clone()),
+            self.weight,
+            self.max_iteration,
+            self.timeout,
+            self.fix,
+            self.trace,
+            self.thread,
+        )?;
+        puzzle.on_improvement = self.on_improvement;
+        Ok(puzzle)
+    }
+}
+
+/*
+ * A number of threads, at least 1, or "auto" for one per core
+ */
+pub fn threads(s: String) -> Result<usize, NPuzzleError> {
+    match &s.to_lowercase() as &str {
+        "auto" => Ok(thread::available_parallelism().map_or(1, |n| n.get())),
+        n => match n.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(NPuzzleError::InvalidThread(s)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_threads() {
+        assert_eq!(3, threads("3".to_string()).unwrap());
+        assert!(threads("AUTO".to_string()).unwrap() >= 1);
+        assert!(threads("0".to_string()).is_err());
+        assert!(threads("many".to_string()).is_err());
+    }
+}
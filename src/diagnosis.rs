@@ -0,0 +1,181 @@
+use super::goal::*;
+use super::utils::*;
+use std::collections::HashMap;
+use std::fmt;
+
+/*
+ * Exchange of two tiles, given by their cells
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Swap {
+    pub first: (usize, usize),
+    pub second: (usize, usize),
+}
+
+impl Swap {
+    pub fn apply(&self, grid: &[Vec<i64>]) -> Vec<Vec<i64>> {
+        let mut res = grid.to_vec();
+        let ((a, b), (c, d)) = (self.first, self.second);
+        res[a][b] = grid[c][d];
+        res[c][d] = grid[a][b];
+        res
+    }
+}
+
+/*
+ * Why a puzzle cannot be solved, and how it could be
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnosis {
+    pub solvability: Solvability,
+    /* Rows between the initial and goal blank, counted on even widths only */
+    pub blank_rows: usize,
+    /* Generated goals, and whether the puzzle reaches each of them */
+    pub goals: Vec<(&'static str, bool)>,
+    /* Tiles to exchange, with their values */
+    pub fix: Option<(Swap, i64, i64)>,
+}
+
+impl Diagnosis {
+    pub fn new(initial: &[Vec<i64>], goal: &[Vec<i64>]) -> Diagnosis {
+        let size = (initial.len() as i64, initial[0].len() as i64);
+        let solvability = solvability(initial, goal);
+        let (x, _) = find_nb(0, initial);
+        let (gx, _) = find_nb(0, goal);
+        let blank_rows = if size.1 % 2 == 0 {
+            (x - gx).unsigned_abs() as usize
+        } else {
+            0
+        };
+        let goals = [("snail", Goal::Snail), ("standard", Goal::Standard)]
+            .iter()
+            .map(|(name, g)| {
+                let reached = g
+                    .generate(size, initial)
+                    .is_ok_and(|goal| solvable(initial, &goal));
+                (*name, reached)
+            })
+            .collect();
+        let fix = Self::repair(initial, goal).map(|swap| {
+            (
+                swap,
+                initial[swap.first.0][swap.first.1],
+                initial[swap.second.0][swap.second.1],
+            )
+        });
+        Diagnosis {
+            solvability,
+            blank_rows,
+            goals,
+            fix,
+        }
+    }
+
+    /*
+     * Exchanging two tiles flips the permutation parity and leaves the
+     * blank in place: among the swaps reaching the goal, keep the one which
+     * leaves the tiles closest (Manhattan distance) to their goal cells
+     */
+    pub fn repair(initial: &[Vec<i64>], goal: &[Vec<i64>]) -> Option<Swap> {
+        let home: HashMap<i64, (usize, usize)> = goal
+            .iter()
+            .enumerate()
+            .flat_map(|(i, row)| row.iter().enumerate().map(move |(j, &t)| (t, (i, j))))
+            .collect();
+        let distance = |tile: i64, (i, j): (usize, usize)| {
+            let (a, b) = home[&tile];
+            (i as i64 - a as i64).abs() + (j as i64 - b as i64).abs()
+        };
+        let cells: Vec<(usize, usize)> = (0..initial.len())
+            .flat_map(|i| (0..initial[i].len()).map(move |j| (i, j)))
+            .filter(|&(i, j)| initial[i][j] != 0)
+            .collect();
+        let mut swaps: Vec<(i64, Swap)> = vec![];
+        for (n, &first) in cells.iter().enumerate() {
+            for &second in cells[n + 1..].iter() {
+                let (a, b) = (initial[first.0][first.1], initial[second.0][second.1]);
+                let delta = distance(a, second) + distance(b, first)
+                    - distance(a, first)
+                    - distance(b, second);
+                swaps.push((delta, Swap { first, second }));
+            }
+        }
+        swaps.sort_by_key(|&(delta, _)| delta);
+        swaps
+            .into_iter()
+            .map(|(_, swap)| swap)
+            .find(|swap| solvable(&swap.apply(initial), goal))
+    }
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = &self.solvability;
+        writeln!(f, "Inversions : {}", s.inversions)?;
+        writeln!(f, "Blank row contribution : {}", self.blank_rows)?;
+        writeln!(
+            f,
+            "Inversions + blank rows : {} (has to be even)",
+            s.inversions + self.blank_rows
+        )?;
+        writeln!(
+            f,
+            "Permutation : {} transpositions, blank distance : {}",
+            s.transpositions, s.blank_distance
+        )?;
+        for (name, reached) in self.goals.iter() {
+            writeln!(
+                f,
+                "{} goal : {}",
+                name,
+                if *reached { "solvable" } else { "unsolvable" }
+            )?;
+        }
+        match self.fix {
+            Some((swap, a, b)) => write!(
+                f,
+                "Suggested fix : swap tiles {} {:?} and {} {:?} (--fix)",
+                a, swap.first, b, swap.second
+            ),
+            None => write!(f, "No single swap makes it solvable"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_diagnosis() {
+        let initial = &[vec![2, 1, 3], vec![8, 0, 4], vec![7, 6, 5]];
+        let goal = &[vec![1, 2, 3], vec![8, 0, 4], vec![7, 6, 5]];
+        let diagnosis = Diagnosis::new(initial, goal);
+        assert!(!diagnosis.solvability.solvable);
+        assert_eq!(
+            (1, 0),
+            (diagnosis.solvability.inversions, diagnosis.blank_rows)
+        );
+        assert_eq!(vec![("snail", false), ("standard", true)], diagnosis.goals);
+        let (swap, a, b) = diagnosis.fix.unwrap();
+        assert_eq!((2, 1), (a, b));
+        assert_eq!(goal.to_vec(), swap.apply(initial));
+    }
+    #[test]
+    fn test_repair_even_width() {
+        let initial = &[
+            vec![1, 2, 3, 4],
+            vec![5, 6, 7, 8],
+            vec![9, 10, 11, 12],
+            vec![13, 15, 14, 0],
+        ];
+        let goal = &[
+            vec![1, 2, 3, 4],
+            vec![5, 6, 7, 8],
+            vec![9, 10, 11, 12],
+            vec![13, 14, 15, 0],
+        ];
+        assert!(!solvable(initial, goal));
+        let swap = Diagnosis::repair(initial, goal).unwrap();
+        assert_eq!(goal.to_vec(), swap.apply(initial));
+    }
+}
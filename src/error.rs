@@ -0,0 +1,130 @@
+use super::diagnosis::*;
+use super::file::*;
+use super::Stop;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/*
+ * Everything that can go wrong before a search starts
+ */
+#[derive(Debug)]
+pub enum NPuzzleError {
+    /* A file which cannot be read, with its path */
+    Read(String, io::Error),
+    /* A file which cannot be written, with its path */
+    Write(String, io::Error),
+    Parse(Box<FileParsingError>),
+    /* A goal file which cannot be parsed, with its path */
+    GoalParse(String, Box<FileParsingError>),
+    GoalMismatch,
+    Unsolvable(Box<Diagnosis>),
+    BoardTooLarge,
+    UnknownAlgorithm(String),
+    UnknownHeuristique(String),
+    UnknownStrategy(String),
+    /* A strategy weighting tiles, given a heuristique of the whole grid */
+    TiledStrategy(String),
+    UnknownConvention(String),
+    UnknownOutput(String),
+    UnknownFormat(String),
+    InvalidThread(String),
+    /* A JSON input which cannot be read as a puzzle */
+    Json(String),
+    /* A puzzle which cannot be generated as asked */
+    Generate(String),
+    /* An optimal distance measure which stopped before the end */
+    DistanceUnknown(Stop),
+    InvalidPartition(String),
+    PatternTooLarge(Vec<i64>),
+    /* A board whose configurations cannot all be enumerated, rows and cols */
+    TooManyStates(i64, i64),
+    /* A command line which cannot be understood */
+    Usage(String),
+    /* A move string with something else than U, D, L or R */
+    InvalidMove(String),
+}
+
+impl fmt::Display for NPuzzleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NPuzzleError::Read(path, e) => write!(f, "Cannot read the file {} : {}", path, e),
+            NPuzzleError::Write(path, e) => write!(f, "Cannot write the file {} : {}", path, e),
+            NPuzzleError::Parse(e) => write!(f, "{}", e),
+            NPuzzleError::GoalParse(path, e) => {
+                let span = e.span();
+                write!(
+                    f,
+                    "Goal file {}:{}:{} : {}",
+                    path, span.line, span.column, e
+                )
+            }
+            NPuzzleError::GoalMismatch => {
+                write!(f, "The Initial State and the Goal State do not correspond")
+            }
+            NPuzzleError::Unsolvable(_) => write!(f, "Unsolvable puzzle"),
+            NPuzzleError::BoardTooLarge => write!(
+                f,
+                "Boards are limited to 256 cells, with tiles numbered up to 255"
+            ),
+            NPuzzleError::UnknownAlgorithm(s) => write!(f, "Algorithm not recognized : {}", s),
+            NPuzzleError::UnknownHeuristique(s) => {
+                write!(f, "Heuristique not recognized : {}", s)
+            }
+            NPuzzleError::UnknownStrategy(s) => write!(f, "Strategy not recognized : {}", s),
+            NPuzzleError::TiledStrategy(s) => {
+                write!(f, "Strategy {} needs a heuristique summed tile by tile", s)
+            }
+            NPuzzleError::UnknownConvention(s) => {
+                write!(f, "Move convention not recognized (blank or tile) : {}", s)
+            }
+            NPuzzleError::UnknownFormat(s) => {
+                write!(f, "Format not recognized (text or json) : {}", s)
+            }
+            NPuzzleError::InvalidThread(s) => write!(
+                f,
+                "Number of threads not recognized (a positive number or auto) : {}",
+                s
+            ),
+            NPuzzleError::Json(s) => write!(f, "Invalid JSON puzzle : {}", s),
+            NPuzzleError::Generate(s) => write!(f, "Cannot generate the puzzle : {}", s),
+            NPuzzleError::DistanceUnknown(stop) => {
+                write!(f, "The optimal distance could not be measured : {}", stop)
+            }
+            NPuzzleError::UnknownOutput(s) => {
+                write!(f, "Output not recognized (moves, grids or both) : {}", s)
+            }
+            NPuzzleError::InvalidPartition(s) => {
+                write!(f, "Invalid pattern database partition : {}", s)
+            }
+            NPuzzleError::PatternTooLarge(pattern) => {
+                write!(f, "Pattern {:?} is too large for this board", pattern)
+            }
+            NPuzzleError::TooManyStates(rows, cols) => write!(
+                f,
+                "A {}x{} board has too many configurations to enumerate them all",
+                rows, cols
+            ),
+            NPuzzleError::Usage(s) => write!(f, "{}", s),
+            NPuzzleError::InvalidMove(s) => {
+                write!(f, "Move not recognized (U, D, L or R) : {}", s)
+            }
+        }
+    }
+}
+
+impl Error for NPuzzleError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            NPuzzleError::Read(_, e) | NPuzzleError::Write(_, e) => Some(e),
+            NPuzzleError::Parse(e) | NPuzzleError::GoalParse(_, e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<FileParsingError> for NPuzzleError {
+    fn from(e: FileParsingError) -> NPuzzleError {
+        NPuzzleError::Parse(Box::new(e))
+    }
+}
@@ -1,47 +1,429 @@
-use std::error::Error;
+use std::collections::HashMap;
 use std::fmt;
 
+/*
+ * (rows, columns) and the tiles
+ */
+pub type ParsedMap = ((i64, i64), Vec<Vec<i64>>);
+
+/*
+ * Other notations of the empty space, read as 0
+ */
+const BLANKS: [&str; 3] = ["_", ".", "X"];
+
+/*
+ * Where an error was found: 1-based line and column, and the token there
+ * (empty when something is missing at the end of a line or of the file)
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub token: String,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum FileParsingError {
-    EmptyMap,
-    BadSize,
-    NoEmptySpace,
+    EmptyMap(Span),
+    BadSize(Span),
+    NoEmptySpace(Span),
+    InvalidNumber(Span),
+    NegativeNumber(Span),
+    /* The header announces `expected` rows (or columns), `found` were given */
+    SizeMismatch(Span, &'static str, usize, usize),
+    /*
+     * At the second occurrence of a tile: where the first one is, every
+     * duplicated value and every label of 0..rows*cols left out
+     */
+    DuplicateTile(Span, (usize, usize), Vec<i64>, Vec<i64>),
+    /* At a tile out of 0..rows*cols: every such tile, every label left out */
+    MissingTile(Span, Vec<i64>, Vec<i64>),
+}
+
+impl FileParsingError {
+    pub fn span(&self) -> &Span {
+        match self {
+            FileParsingError::EmptyMap(span)
+            | FileParsingError::BadSize(span)
+            | FileParsingError::NoEmptySpace(span)
+            | FileParsingError::InvalidNumber(span)
+            | FileParsingError::NegativeNumber(span)
+            | FileParsingError::SizeMismatch(span, _, _, _)
+            | FileParsingError::DuplicateTile(span, _, _, _)
+            | FileParsingError::MissingTile(span, _, _) => span,
+        }
+    }
 }
 
 impl fmt::Display for FileParsingError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self {
-            FileParsingError::EmptyMap => write!(f, "It seems that the input file is empty"),
-            FileParsingError::BadSize => write!(f, "Map size is incorrect."),
-            FileParsingError::NoEmptySpace => write!(f, "The empty space (0) is missing"),
+            FileParsingError::EmptyMap(_) => write!(f, "It seems that the input file is empty"),
+            FileParsingError::BadSize(span) => write!(
+                f,
+                "Map size is incorrect: expected \"size\" or \"rows cols\", found `{}`",
+                span.token
+            ),
+            FileParsingError::NoEmptySpace(span) => write!(
+                f,
+                "The empty space (0) is missing, `{}` is out of range",
+                span.token
+            ),
+            FileParsingError::InvalidNumber(span) => {
+                write!(f, "`{}` is not a number", span.token)
+            }
+            FileParsingError::NegativeNumber(span) => {
+                write!(f, "`{}` is negative", span.token)
+            }
+            FileParsingError::SizeMismatch(_, what, expected, found) => write!(
+                f,
+                "The size header announces {} {} but {} were found",
+                expected, what, found
+            ),
+            FileParsingError::DuplicateTile(span, (line, column), duplicated, missing) => write!(
+                f,
+                "Tile {} is duplicated, first seen at {}:{} (duplicated: {}; missing: {})",
+                span.token,
+                line,
+                column,
+                list(duplicated),
+                list(missing)
+            ),
+            FileParsingError::MissingTile(_, out_of_range, missing) => write!(
+                f,
+                "Tiles have to be numbered from 0 (out of range: {}; missing: {})",
+                list(out_of_range),
+                list(missing)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FileParsingError {}
+
+fn list(values: &[i64]) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/*
+ * Tokens of a line with their 1-based column, comments stripped
+ */
+fn tokens(line: &str) -> Vec<(usize, &str)> {
+    let content = line.split('#').next().unwrap();
+    let mut res = vec![];
+    let mut start: Option<usize> = None;
+    for (i, c) in content.char_indices().chain(Some((content.len(), ' '))) {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(i),
+            (Some(s), true) => {
+                res.push((s, &content[s..i]));
+                start = None;
+            }
+            _ => {}
         }
     }
+    res.into_iter()
+        .map(|(i, token)| (content[..i].chars().count() + 1, token))
+        .collect()
+}
+
+fn number(line: usize, (column, token): (usize, &str)) -> Result<i64, FileParsingError> {
+    let span = Span {
+        line,
+        column,
+        token: token.to_string(),
+    };
+    if BLANKS.contains(&token) {
+        return Ok(0);
+    }
+    match token.parse::<i64>() {
+        Ok(n) if n < 0 => Err(FileParsingError::NegativeNumber(span)),
+        Ok(n) => Ok(n),
+        Err(_) => Err(FileParsingError::InvalidNumber(span)),
+    }
+}
+
+/*
+ * Puzzles of a file holding several of them, one after the other: each
+ * with the line it starts on, padded with empty lines so that parse errors
+ * keep their position in the file. A header which cannot be read swallows
+ * the lines up to the next empty one.
+ */
+pub fn split_puzzles(arg: &str) -> Vec<(usize, String)> {
+    let mut res = vec![];
+    let mut current: Option<(usize, String, Option<usize>)> = None;
+    for (i, line) in arg.lines().enumerate() {
+        let tokens = tokens(line);
+        let (start, mut chunk, rows) = match current.take() {
+            Some(current) => current,
+            None if tokens.is_empty() => continue,
+            None => {
+                let rows = tokens[0].1.parse::<usize>().ok().filter(|&n| n > 0);
+                (i + 1, "\n".repeat(i), rows.map(|n| n + 1))
+            }
+        };
+        chunk.push_str(line);
+        chunk.push('\n');
+        let rows = match rows {
+            Some(n) if !tokens.is_empty() => Some(n - 1),
+            None if line.trim().is_empty() => Some(0),
+            rows => rows,
+        };
+        match rows {
+            Some(0) => res.push((start, chunk)),
+            rows => current = Some((start, chunk, rows)),
+        }
+    }
+    if let Some((start, chunk, _)) = current {
+        res.push((start, chunk));
+    }
+    res
+}
+
+/*
+ * Header and right-aligned rows of the grid, as parse_file reads them
+ */
+pub fn format_map(tiles: &[Vec<i64>]) -> String {
+    let (rows, cols) = (tiles.len(), tiles[0].len());
+    let width = (rows * cols - 1).to_string().len();
+    let mut res = match rows == cols {
+        true => format!("{}\n", rows),
+        false => format!("{} {}\n", rows, cols),
+    };
+    for row in tiles.iter() {
+        let row: Vec<String> = row
+            .iter()
+            .map(|t| format!("{:>width$}", t, width = width))
+            .collect();
+        res += &format!("{}\n", row.join(" "));
+    }
+    res
 }
 
-impl Error for FileParsingError {}
+/*
+ * Byte order marks are dropped, tabs are spaces, comment-only lines are
+ * skipped and the empty space may be written 0, _, . or X
+ */
+pub fn parse_file(arg: String) -> Result<ParsedMap, FileParsingError> {
+    parse(arg.strip_prefix('\u{feff}').unwrap_or(&arg))
+}
 
-pub fn parse_file(arg: String) -> Result<(i64, Vec<Vec<i64>>), Box<dyn Error>> {
-    let initial: Result<Vec<Vec<i64>>, _> = arg
+fn parse(arg: &str) -> Result<ParsedMap, FileParsingError> {
+    let span = |line: usize, column: usize, token: &str| Span {
+        line,
+        column,
+        token: token.to_string(),
+    };
+    let mut lines = arg
         .lines()
-        .map(|x| x.split('#').next().unwrap().trim())
-        .filter(|x| !x.is_empty())
-        .map(|x| x.split_whitespace().map(|x| x.parse::<i64>()).collect())
-        .collect();
-    let mut initial = initial?;
-    if initial.len() == 0 {
-        return Err(Box::new(FileParsingError::EmptyMap));
-    }
-    let mut size = initial.remove(0);
-    if size.len() > 1 {
-        return Err(Box::new(FileParsingError::BadSize));
-    }
-    let size = size.remove(0);
-    if size <= 0 || size != initial.len() as i64 || !initial.iter().all(|x| x.len() as i64 == size)
-    {
-        return Err(Box::new(FileParsingError::BadSize));
-    }
-    if !initial.iter().any(|x| x.iter().any(|y| y == &0)) {
-        return Err(Box::new(FileParsingError::NoEmptySpace));
+        .enumerate()
+        .map(|(i, x)| (i + 1, tokens(x)))
+        .filter(|(_, x)| !x.is_empty());
+
+    // Either "size" for a square board or "rows cols"
+    let (header, sizes) = match lines.next() {
+        Some(header) => header,
+        None => {
+            return Err(FileParsingError::EmptyMap(span(
+                arg.lines().count() + 1,
+                1,
+                "",
+            )))
+        }
+    };
+    if sizes.len() > 2 {
+        let (column, token) = sizes[2];
+        return Err(FileParsingError::BadSize(span(header, column, token)));
+    }
+    let mut dims = vec![];
+    for &token in sizes.iter() {
+        match number(header, token)? {
+            0 => return Err(FileParsingError::BadSize(span(header, token.0, token.1))),
+            n => dims.push(n),
+        }
+    }
+    let size = (dims[0], *dims.last().unwrap());
+
+    let mut initial: Vec<Vec<i64>> = vec![];
+    let mut seen: HashMap<i64, (usize, usize)> = HashMap::new();
+    let mut duplicate: Option<(Span, (usize, usize))> = None;
+    let mut duplicated: Vec<i64> = vec![];
+    let mut out_of_range: Option<Span> = None;
+    for (line, row) in lines {
+        if initial.len() as i64 == size.0 {
+            let (column, token) = row[0];
+            return Err(FileParsingError::SizeMismatch(
+                span(line, column, token),
+                "rows",
+                size.0 as usize,
+                initial.len() + 1,
+            ));
+        }
+        if row.len() as i64 != size.1 {
+            let text = arg.lines().nth(line - 1).unwrap();
+            let (column, token) = match row.get(size.1 as usize) {
+                Some(&extra) => extra,
+                None => (
+                    text.split('#').next().unwrap().trim_end().chars().count() + 1,
+                    "",
+                ),
+            };
+            return Err(FileParsingError::SizeMismatch(
+                span(line, column, token),
+                "columns",
+                size.1 as usize,
+                row.len(),
+            ));
+        }
+        let mut tiles = vec![];
+        for &(column, token) in row.iter() {
+            let tile = number(line, (column, token))?;
+            match seen.get(&tile) {
+                Some(&first) => {
+                    if duplicate.is_none() {
+                        duplicate = Some((span(line, column, token), first));
+                    }
+                    duplicated.push(tile);
+                }
+                None => {
+                    seen.insert(tile, (line, column));
+                }
+            }
+            if tile >= size.0 * size.1 && out_of_range.is_none() {
+                out_of_range = Some(span(line, column, token));
+            }
+            tiles.push(tile);
+        }
+        initial.push(tiles);
+    }
+    if (initial.len() as i64) < size.0 {
+        let (column, token) = sizes[0];
+        return Err(FileParsingError::SizeMismatch(
+            span(header, column, token),
+            "rows",
+            size.0 as usize,
+            initial.len(),
+        ));
+    }
+    let cells = size.0 * size.1;
+    let missing: Vec<i64> = (0..cells).filter(|t| !seen.contains_key(t)).collect();
+    if let Some((span, first)) = duplicate {
+        duplicated.sort();
+        duplicated.dedup();
+        return Err(FileParsingError::DuplicateTile(
+            span, first, duplicated, missing,
+        ));
+    }
+    if let Some(span) = out_of_range {
+        return Err(match &missing[..] {
+            [0] => FileParsingError::NoEmptySpace(span),
+            _ => {
+                let mut extra: Vec<i64> = seen.keys().cloned().filter(|&t| t >= cells).collect();
+                extra.sort();
+                FileParsingError::MissingTile(span, extra, missing)
+            }
+        });
     }
     Ok((size, initial))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(arg: &str) -> (FileParsingError, (usize, usize, String)) {
+        let err = parse(arg).unwrap_err();
+        let span = err.span().clone();
+        (err, (span.line, span.column, span.token))
+    }
+
+    #[test]
+    fn test_parse_rectangle() {
+        let (size, grid) = parse("# comment\n2 3\n1 2 3 # row\n4 5 0\n").unwrap();
+        assert_eq!((2, 3), size);
+        assert_eq!(vec![vec![1, 2, 3], vec![4, 5, 0]], grid);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let (err, at) = error("3\n1 1 2\n3 4 5\n7 6 0\n");
+        assert_eq!(
+            FileParsingError::DuplicateTile(err.span().clone(), (2, 1), vec![1], vec![8]),
+            err
+        );
+        assert_eq!((2, 3, "1".to_string()), at);
+        let (err, _) = error("3\n1 1 2\n3 3 5\n7 6 0\n");
+        assert!(matches!(
+            err,
+            FileParsingError::DuplicateTile(_, (2, 1), ref d, ref m) if d == &[1, 3] && m == &[4, 8]
+        ));
+        let (err, at) = error("2\n1 2\n3 -4\n");
+        assert!(matches!(err, FileParsingError::NegativeNumber(_)));
+        assert_eq!((3, 3, "-4".to_string()), at);
+        let (err, at) = error("2\n1 x\n3 0\n");
+        assert!(matches!(err, FileParsingError::InvalidNumber(_)));
+        assert_eq!((2, 3, "x".to_string()), at);
+        let (err, at) = error("3\n1 2 3\n4 5 6\n");
+        assert!(matches!(
+            err,
+            FileParsingError::SizeMismatch(_, "rows", 3, 2)
+        ));
+        assert_eq!((1, 1, "3".to_string()), at);
+        let (err, at) = error("2\n1 2\n3\n");
+        assert!(matches!(
+            err,
+            FileParsingError::SizeMismatch(_, "columns", 2, 1)
+        ));
+        assert_eq!((3, 2, "".to_string()), at);
+        let (err, at) = error("2\n1 2\n3 7\n");
+        assert_eq!(FileParsingError::NoEmptySpace(err.span().clone()), err);
+        assert_eq!((3, 3, "7".to_string()), at);
+        let (err, _) = error("2\n0 9\n3 7\n");
+        assert!(matches!(
+            err,
+            FileParsingError::MissingTile(_, ref o, ref m) if o == &[7, 9] && m == &[1, 2]
+        ));
+        let (err, _) = error("# nothing\n\n");
+        assert!(matches!(err, FileParsingError::EmptyMap(_)));
+    }
+
+    #[test]
+    fn test_parse_notations() {
+        let map = parse_file(
+            "\u{feff}# blank as _\n3\n1\t2 3\n  # comment only\n8 _ 4\n7 6 5\n".to_string(),
+        );
+        assert_eq!(
+            ((3, 3), vec![vec![1, 2, 3], vec![8, 0, 4], vec![7, 6, 5]]),
+            map.unwrap()
+        );
+        assert_eq!(vec![vec![1, 0]], parse("1 2\n1 .").unwrap().1);
+        assert_eq!(vec![vec![0, 1]], parse("1 2\nX 1").unwrap().1);
+        let (err, _) = error("1 2\n_ X\n");
+        assert!(matches!(err, FileParsingError::DuplicateTile(..)));
+    }
+
+    #[test]
+    fn test_split_puzzles() {
+        let puzzles = split_puzzles("# first\n2\n1 2\n3 0\n\n# second\n2 3\n1 2 3\n# in\n4 5 0\n");
+        assert_eq!(
+            vec![2, 7],
+            puzzles
+                .iter()
+                .map(|(line, _)| *line)
+                .collect::<Vec<usize>>()
+        );
+        assert_eq!((2, 3), parse(&puzzles[1].1).unwrap().0);
+        let puzzles = split_puzzles("x\n1 2\n\n1\n0\n");
+        assert_eq!(2, puzzles.len());
+        assert_eq!((1, 1, "x".to_string()), {
+            let err = parse(&puzzles[0].1).unwrap_err();
+            let span = err.span().clone();
+            (span.line, span.column, span.token)
+        });
+        assert_eq!(((1, 1), vec![vec![0]]), parse(&puzzles[1].1).unwrap());
+    }
+}
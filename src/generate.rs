@@ -0,0 +1,357 @@
+use super::algorithm::*;
+use super::board::*;
+use super::error::*;
+use super::file::*;
+use super::goal::*;
+use super::heuristique::*;
+use super::utils::*;
+use super::NPuzzle;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::time::Instant;
+
+/*
+ * How the goal is scrambled
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scramble {
+    /* Uniform over the arrangements of the wanted solvability */
+    Shuffle,
+    /* Random walk of the blank, never undoing the previous move */
+    Moves(usize),
+    /* Exactly this many moves away from the goal, optimally */
+    Distance(usize),
+}
+
+/*
+ * A generated puzzle, with what is known about it
+ */
+#[derive(Debug, Clone)]
+pub struct Generated {
+    pub size: (i64, i64),
+    pub tiles: Vec<Vec<i64>>,
+    pub goal: Vec<Vec<i64>>,
+    pub solvable: bool,
+    pub seed: u64,
+    /* Optimal number of moves, when it was verified */
+    pub distance: Option<usize>,
+}
+
+impl Generated {
+    /*
+     * Text accepted by parse_file
+     */
+    pub fn to_text(&self) -> String {
+        let mut res = format!(
+            "# This puzzle is {}\n# seed: {}\n",
+            if self.solvable {
+                "solvable"
+            } else {
+                "unsolvable"
+            },
+            self.seed
+        );
+        if let Some(distance) = self.distance {
+            res += &format!("# distance: {}\n", distance);
+        }
+        res + &format_map(&self.tiles)
+    }
+}
+
+pub struct Generator {
+    size: (i64, i64),
+    goal: Vec<Vec<i64>>,
+    /* Guides the IDA* measuring the optimal distance for Scramble::Distance */
+    oracle: Heuristique,
+    seed: u64,
+    rng: StdRng,
+}
+
+impl Generator {
+    /*
+     * A random seed is drawn when none is given, and reported in every
+     * generated puzzle so that it can be reproduced.
+     * The heuristique only speeds up the distance measures: one which could
+     * overestimate it gives way to the linear conflict one.
+     */
+    pub fn new(
+        size: (i64, i64),
+        goal: &Goal,
+        heuristique: Heuristique,
+        seed: Option<u64>,
+    ) -> Result<Generator, NPuzzleError> {
+        if size.0 <= 0 || size.1 <= 0 || size.0 * size.1 > 256 {
+            return Err(NPuzzleError::BoardTooLarge);
+        }
+        let tiles: Vec<Vec<i64>> = (0..size.0)
+            .map(|i| (0..size.1).map(|j| i * size.1 + j).collect())
+            .collect();
+        let goal = goal.generate(size, &tiles)?;
+        let mut oracle = match heuristique.admissible() {
+            true => heuristique,
+            false => Heuristique::LinearConflict,
+        };
+        oracle.init(&Board::new(&goal).ok_or(NPuzzleError::BoardTooLarge)?)?;
+        let seed = seed.unwrap_or_else(|| rand::thread_rng().gen());
+        Ok(Generator {
+            size,
+            goal,
+            oracle,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        })
+    }
+
+    /*
+     * Solvable or not as asked, at random when solvable is None (but for
+     * an exact distance, which only solvable puzzles have)
+     */
+    pub fn generate(
+        &mut self,
+        scramble: Scramble,
+        solvable: Option<bool>,
+    ) -> Result<Generated, NPuzzleError> {
+        let solvable = match (solvable, scramble) {
+            (Some(solvable), _) => solvable,
+            (None, Scramble::Distance(_)) => true,
+            (None, _) => self.rng.gen(),
+        };
+        let line = self.size.0 == 1 || self.size.1 == 1;
+        let mut distance = None;
+        let mut tiles = match scramble {
+            // Only the blank moves on a single line
+            Scramble::Shuffle if line => self.walk((self.size.0 * self.size.1) as usize),
+            Scramble::Shuffle => {
+                let mut flat: Vec<i64> = self.goal.iter().flatten().cloned().collect();
+                flat.shuffle(&mut self.rng);
+                flat.chunks(self.size.1 as usize)
+                    .map(|row| row.to_vec())
+                    .collect()
+            }
+            Scramble::Moves(k) => self.walk(k),
+            Scramble::Distance(_) if !solvable => {
+                return Err(NPuzzleError::Generate(
+                    "an unsolvable puzzle has no distance to the goal".to_string(),
+                ))
+            }
+            Scramble::Distance(d) => {
+                distance = Some(d);
+                self.climb(d)?
+            }
+        };
+        if solvable != self.solvable(&tiles) {
+            tiles = self.swap(&tiles)?;
+        }
+        Ok(Generated {
+            size: self.size,
+            tiles,
+            goal: self.goal.clone(),
+            solvable,
+            seed: self.seed,
+            distance,
+        })
+    }
+
+    fn solvable(&self, tiles: &[Vec<i64>]) -> bool {
+        solvable(tiles, &self.goal)
+    }
+
+    /*
+     * Exchange the first two tiles (not the blank): the permutation parity
+     * flips, and so does the solvability
+     */
+    fn swap(&self, tiles: &[Vec<i64>]) -> Result<Vec<Vec<i64>>, NPuzzleError> {
+        let cells: Vec<(usize, usize)> = (0..tiles.len())
+            .flat_map(|i| (0..tiles[i].len()).map(move |j| (i, j)))
+            .filter(|&(i, j)| tiles[i][j] != 0)
+            .take(2)
+            .collect();
+        if cells.len() < 2 {
+            return Err(NPuzzleError::Generate(
+                "every arrangement of this board is solvable".to_string(),
+            ));
+        }
+        let mut res = tiles.to_vec();
+        let ((a, b), (c, d)) = (cells[0], cells[1]);
+        res[a][b] = tiles[c][d];
+        res[c][d] = tiles[a][b];
+        Ok(res)
+    }
+
+    fn walk(&mut self, moves: usize) -> Vec<Vec<i64>> {
+        let mut board = Board::new(&self.goal).unwrap();
+        let mut from = None;
+        for _ in 0..moves {
+            let blank = board.blank();
+            let mut cells = neighbours(self.size, blank, from);
+            // At the end of a single line, going back is the only move
+            if cells.is_empty() {
+                cells = neighbours(self.size, blank, None);
+            }
+            match cells.choose(&mut self.rng) {
+                Some(&cell) => {
+                    board = board.slide(cell);
+                    from = Some(blank);
+                }
+                None => break,
+            }
+        }
+        board.grid()
+    }
+
+    /*
+     * Walk away from the goal one optimal move at a time: a move changes the
+     * optimal distance by exactly one, so a neighbour which is not closer is
+     * one move further. Restart from the goal when stuck on a local maximum.
+     */
+    fn climb(&mut self, target: usize) -> Result<Vec<Vec<i64>>, NPuzzleError> {
+        const ATTEMPTS: usize = 32;
+        for _ in 0..ATTEMPTS {
+            let mut board = Board::new(&self.goal).unwrap();
+            let mut distance = 0;
+            while distance < target {
+                match self.further(&board, distance, None)? {
+                    Some(next) => {
+                        board = next;
+                        distance += 1;
+                    }
+                    None => break,
+                }
+            }
+            if distance == target {
+                return Ok(board.grid());
+            }
+        }
+        Err(NPuzzleError::Generate(format!(
+            "no puzzle {} moves away from the goal was found",
+            target
+        )))
+    }
+
+    /*
+     * A random neighbour one move further from the goal than board, which
+     * is distance moves away, if there is one
+     */
+    pub(crate) fn further(
+        &mut self,
+        board: &Board,
+        distance: usize,
+        deadline: Option<Instant>,
+    ) -> Result<Option<Board>, NPuzzleError> {
+        let mut cells = neighbours(self.size, board.blank(), None);
+        cells.shuffle(&mut self.rng);
+        for next in cells.into_iter().map(|cell| board.slide(cell)) {
+            if self.distance(&next, deadline)? > distance as f64 {
+                return Ok(Some(next));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn goal(&self) -> &[Vec<i64>] {
+        &self.goal
+    }
+
+    /*
+     * Optimal number of moves, found by IDA* unless the deadline passes
+     */
+    pub(crate) fn distance(
+        &self,
+        board: &Board,
+        deadline: Option<Instant>,
+    ) -> Result<f64, NPuzzleError> {
+        let mut puzzle = NPuzzle::new(
+            ((self.size.0, self.size.1), board.grid()),
+            self.oracle.clone(),
+            Algorithm::IdaStar,
+            "std".to_string(),
+            Goal::Tiles(self.goal.clone()),
+            1.0,
+            u64::MAX,
+            deadline.map(|d| d.saturating_duration_since(Instant::now())),
+            false,
+            None,
+            1,
+        )?;
+        let solution = puzzle.run()?;
+        match solution.stop {
+            Some(stop) if !solution.solved => Err(NPuzzleError::DistanceUnknown(stop)),
+            _ => Ok(solution.cost),
+        }
+    }
+}
+
+/*
+ * Cells the blank can move to from (i, j), but the one it comes from
+ */
+pub(crate) fn neighbours(
+    (rows, cols): (i64, i64),
+    (i, j): (usize, usize),
+    from: Option<(usize, usize)>,
+) -> Vec<(usize, usize)> {
+    let (rows, cols) = (rows as usize, cols as usize);
+    let mut res = vec![];
+    if i > 0 {
+        res.push((i - 1, j));
+    }
+    if i + 1 < rows {
+        res.push((i + 1, j));
+    }
+    if j > 0 {
+        res.push((i, j - 1));
+    }
+    if j + 1 < cols {
+        res.push((i, j + 1));
+    }
+    res.into_iter().filter(|&cell| Some(cell) != from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_generate() {
+        let mut generator =
+            Generator::new((3, 3), &Goal::Snail, Heuristique::Manhattan, Some(42)).unwrap();
+        let first = generator.generate(Scramble::Shuffle, Some(true)).unwrap();
+        assert!(solvable(&first.tiles, &first.goal));
+        let unsolvable = generator
+            .generate(Scramble::Moves(20), Some(false))
+            .unwrap();
+        assert!(!solvable(&unsolvable.tiles, &unsolvable.goal));
+        let again = Generator::new((3, 3), &Goal::Snail, Heuristique::Manhattan, Some(42))
+            .unwrap()
+            .generate(Scramble::Shuffle, Some(true))
+            .unwrap();
+        assert_eq!(first.tiles, again.tiles);
+
+        let mut generator =
+            Generator::new((2, 3), &Goal::Standard, Heuristique::Manhattan, None).unwrap();
+        let far = generator
+            .generate(Scramble::Distance(12), Some(true))
+            .unwrap();
+        assert_eq!(
+            12.0,
+            generator
+                .distance(&Board::new(&far.tiles).unwrap(), None)
+                .unwrap()
+        );
+        // Hamming would overestimate the distances
+        let mut generator =
+            Generator::new((2, 3), &Goal::Standard, Heuristique::Hamming, Some(7)).unwrap();
+        assert!(matches!(generator.oracle, Heuristique::LinearConflict));
+        let far = generator
+            .generate(Scramble::Distance(10), Some(true))
+            .unwrap();
+        assert_eq!(
+            10.0,
+            generator
+                .distance(&Board::new(&far.tiles).unwrap(), None)
+                .unwrap()
+        );
+        assert!(generator
+            .generate(Scramble::Distance(50), Some(true))
+            .is_err());
+    }
+}
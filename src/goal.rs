@@ -1,11 +1,14 @@
+use super::error::*;
 use super::file::*;
 use std::fs;
-use std::process;
 
+#[derive(Debug, Clone)]
 pub enum Goal {
     Snail,
     Standard,
     Custom(String),
+    /* Given directly, as in a JSON input */
+    Tiles(Vec<Vec<i64>>),
 }
 
 impl Goal {
@@ -20,86 +23,84 @@ impl Goal {
     /*
      * Generate the Goal/Final State
      */
-    pub fn generate(&self, size: i64, map: &Vec<Vec<i64>>) -> Vec<Vec<i64>> {
-        let mut f: Vec<i64> = map.clone().into_iter().flatten().collect();
+    pub fn generate(
+        &self,
+        size: (i64, i64),
+        map: &[Vec<i64>],
+    ) -> Result<Vec<Vec<i64>>, NPuzzleError> {
+        let mut f: Vec<i64> = map.iter().flatten().cloned().collect();
         f.sort();
         let zero = f.remove(0);
         f.push(zero);
         let res = match &self {
             Goal::Snail => Self::generate_snail(size, &f),
             Goal::Standard => Self::generate_std(size, &f),
-            Goal::Custom(file) => Self::generate_custom(file),
+            Goal::Custom(file) => Self::generate_custom(file)?,
+            Goal::Tiles(tiles) => tiles.clone(),
         };
         let mut tmp = res.clone().into_iter().flatten().collect::<Vec<i64>>();
         tmp.sort();
         let zero = tmp.remove(0);
         tmp.push(zero);
-        if tmp == f {
-            res
+        if tmp == f && res.len() as i64 == size.0 && res.iter().all(|x| x.len() as i64 == size.1) {
+            Ok(res)
         } else {
-            println!("The Initial State and the Goal State do not correspond");
-            process::exit(1);
+            Err(NPuzzleError::GoalMismatch)
         }
     }
 
-    fn generate_snail(size: i64, map: &Vec<i64>) -> Vec<Vec<i64>> {
+    /*
+     * Tiles laid clockwise from the top left corner, turning inwards
+     */
+    fn generate_snail((rows, cols): (i64, i64), map: &[i64]) -> Vec<Vec<i64>> {
         let mut map = map.iter();
 
-        let mut a: Vec<Vec<i64>> = vec![vec![0; size as usize]; size as usize];
-        let n = size;
-        let mut len = size;
-        let mut k = 0;
-        let mut p = 0;
+        let mut a: Vec<Vec<i64>> = vec![vec![0; cols as usize]; rows as usize];
+        let (mut top, mut bottom) = (0, rows - 1);
+        let (mut left, mut right) = (0, cols - 1);
 
-        while k < n * n {
-            for i in p..len {
-                a[p as usize][i as usize] = *map.next().unwrap();
-                k += 1;
+        while top <= bottom && left <= right {
+            for j in left..=right {
+                a[top as usize][j as usize] = *map.next().unwrap();
             }
+            top += 1;
 
-            for i in p + 1..len {
-                a[i as usize][(len - 1) as usize] = *map.next().unwrap();
-                k += 1;
+            for i in top..=bottom {
+                a[i as usize][right as usize] = *map.next().unwrap();
             }
+            right -= 1;
 
-            for i in (p..len - 1).rev() {
-                a[(len - 1) as usize][i as usize] = *map.next().unwrap();
-                k += 1
+            if top <= bottom {
+                for j in (left..=right).rev() {
+                    a[bottom as usize][j as usize] = *map.next().unwrap();
+                }
+                bottom -= 1;
             }
 
-            for i in (p + 1..len - 1).rev() {
-                a[i as usize][p as usize] = *map.next().unwrap();
-                k += 1;
+            if left <= right {
+                for i in (top..=bottom).rev() {
+                    a[i as usize][left as usize] = *map.next().unwrap();
+                }
+                left += 1;
             }
-
-            p += 1;
-            len -= 1;
         }
         a
     }
 
-    fn generate_std(size: i64, map: &Vec<i64>) -> Vec<Vec<i64>> {
-        let mut res: Vec<Vec<i64>> = vec![vec![]; size as usize];
+    fn generate_std((rows, cols): (i64, i64), map: &[i64]) -> Vec<Vec<i64>> {
+        let mut res: Vec<Vec<i64>> = vec![vec![]; rows as usize];
 
         for (i, c) in map.iter().enumerate() {
-            res[i / size as usize].push(*c);
+            res[i / cols as usize].push(*c);
         }
         res
     }
 
-    fn generate_custom(file: &str) -> Vec<Vec<i64>> {
-        println!("CUSTOM : {}", file);
-        if let Ok(m) = fs::read_to_string(file) {
-            match parse_file(m) {
-                Ok((_, v)) => v,
-                Err(e) => {
-                    println!("Goal file has these issue : {}", e);
-                    process::exit(1);
-                }
-            }
-        } else {
-            println!("Cannot read the file : {}", file);
-            process::exit(1);
+    fn generate_custom(file: &str) -> Result<Vec<Vec<i64>>, NPuzzleError> {
+        let m = fs::read_to_string(file).map_err(|e| NPuzzleError::Read(file.to_string(), e))?;
+        match parse_file(m) {
+            Ok((_, v)) => Ok(v),
+            Err(e) => Err(NPuzzleError::GoalParse(file.to_string(), Box::new(e))),
         }
     }
 }
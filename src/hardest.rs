@@ -0,0 +1,125 @@
+use super::board::*;
+use super::error::*;
+use super::file::*;
+use super::generate::*;
+use super::perfect::*;
+use std::time::Instant;
+
+/*
+ * A far configuration and its optimal number of moves to the goal
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instance {
+    pub tiles: Vec<Vec<i64>>,
+    pub distance: usize,
+}
+
+/*
+ * Every reachable configuration is in the perfect table, with its distance
+ * proven: the top k are taken from the farthest ones.
+ */
+pub fn retrograde(table: &PerfectTable, k: usize) -> Vec<Instance> {
+    let mut res = vec![];
+    for distance in (0..table.histogram().len()).rev() {
+        let mut layer: Vec<Vec<Vec<i64>>> = table.at_distance(distance).collect();
+        layer.sort();
+        for tiles in layer.into_iter().take(k - res.len()) {
+            res.push(Instance { tiles, distance });
+        }
+        if res.len() == k {
+            break;
+        }
+    }
+    res
+}
+
+/*
+ * Restarts from random solvable configurations, each climbing to a
+ * neighbour one optimal move further until none is: a local maximum, whose
+ * distance is exact but not proven to be the largest. Stops after rounds
+ * climbs or once the deadline has passed, dropping the climb it interrupts,
+ * keeping the top k distinct configurations.
+ */
+pub fn hill_climb(
+    generator: &mut Generator,
+    k: usize,
+    rounds: usize,
+    deadline: Option<Instant>,
+) -> Result<Vec<Instance>, NPuzzleError> {
+    let mut res: Vec<Instance> = vec![];
+    for _ in 0..rounds {
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            break;
+        }
+        let start = generator.generate(Scramble::Shuffle, Some(true))?;
+        let mut board = Board::new(&start.tiles).ok_or(NPuzzleError::BoardTooLarge)?;
+        let climb = generator.distance(&board, deadline).and_then(|d| {
+            let mut distance = d as usize;
+            while let Some(next) = generator.further(&board, distance, deadline)? {
+                board = next;
+                distance += 1;
+            }
+            Ok(distance)
+        });
+        let distance = match climb {
+            Ok(distance) => distance,
+            Err(NPuzzleError::DistanceUnknown(_)) => break,
+            Err(err) => return Err(err),
+        };
+        let tiles = board.grid();
+        if res.iter().all(|found| found.tiles != tiles) {
+            res.push(Instance { tiles, distance });
+            res.sort_by_key(|found| std::cmp::Reverse(found.distance));
+            res.truncate(k);
+        }
+    }
+    Ok(res)
+}
+
+/*
+ * Every instance in the format parse_file reads, one after the other
+ */
+pub fn to_text(instances: &[Instance], how: &str) -> String {
+    let mut res = String::new();
+    for instance in instances {
+        res += &format!("# distance: {} ({})\n", instance.distance, how);
+        res += &format_map(&instance.tiles);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::goal::*;
+    use crate::heuristique::*;
+    use std::time::Duration;
+    #[test]
+    fn test_hardest() {
+        let mut generator =
+            Generator::new((2, 3), &Goal::Standard, Heuristique::Manhattan, Some(1)).unwrap();
+        let found = retrograde(&PerfectTable::build(generator.goal()).unwrap(), 3);
+        assert_eq!(3, found.len());
+        assert_eq!(21, found[0].distance);
+        for instance in found.iter() {
+            let board = Board::new(&instance.tiles).unwrap();
+            assert_eq!(
+                instance.distance as f64,
+                generator.distance(&board, None).unwrap()
+            );
+        }
+        let climbed = hill_climb(&mut generator, 2, 4, None).unwrap();
+        assert!(climbed.iter().all(|i| i.distance <= 21));
+        // The deadline interrupts IDA* on a 4x4 board
+        let mut generator =
+            Generator::new((4, 4), &Goal::Standard, Heuristique::Hamming, Some(1)).unwrap();
+        let start = Instant::now();
+        let deadline = Some(start + Duration::from_millis(100));
+        assert!(hill_climb(&mut generator, 2, 1000, deadline).is_ok());
+        assert!(start.elapsed() < Duration::from_secs(5));
+        let text = to_text(&found, "retrograde BFS");
+        let puzzles = split_puzzles(&text);
+        assert_eq!(3, puzzles.len());
+        assert_eq!(found[2].tiles, parse_file(puzzles[2].1.clone()).unwrap().1);
+    }
+}
@@ -1,97 +1,425 @@
-use super::utils::*;
-use std::process;
+use super::board::*;
+use super::error::*;
+use super::pattern::*;
+use super::perfect::*;
+use super::walking::*;
+use std::sync::Arc;
+
+/*
+ * Value of a tile standing on a cell, for heuristiques summed tile by tile
+ */
+type CellValue = fn(i64, (usize, usize), &Homes) -> f64;
 
 #[derive(Debug, Clone)]
 pub enum Heuristique {
     Hamming,
     Manhattan,
     LinearConflict,
+    PatternDatabase(Partition, Option<Arc<PatternDatabase>>),
+    WalkingDistance(Option<Arc<WalkingDistance>>),
+    /* Exact distance read from the table of every configuration */
+    Perfect(Option<Arc<PerfectTable>>),
 }
 
 impl Heuristique {
-    pub fn parse(s: String) -> Heuristique {
-        match &s.to_lowercase() as &str {
+    pub fn parse(s: String) -> Result<Heuristique, NPuzzleError> {
+        Ok(match &s.to_lowercase() as &str {
             "hamming" => Heuristique::Hamming,
             "std" | "manhattan" => Heuristique::Manhattan,
             "linearconflict" => Heuristique::LinearConflict,
-            _ => {
-                println!("Heuristique not recognized");
-                process::exit(1);
+            "walkingdistance" | "wd" => Heuristique::WalkingDistance(None),
+            "perfect" => Heuristique::Perfect(None),
+            name if name == "pdb" || name.starts_with("pdb:") => {
+                match Partition::parse(name.trim_start_matches("pdb").trim_start_matches(':')) {
+                    Some(partition) => Heuristique::PatternDatabase(partition, None),
+                    None => return Err(NPuzzleError::InvalidPartition(s)),
+                }
+            }
+            _ => return Err(NPuzzleError::UnknownHeuristique(s)),
+        })
+    }
+
+    /*
+     * Precompute what the heuristique needs to know about the goal
+     */
+    pub fn init(&mut self, goal: &Board) -> Result<(), NPuzzleError> {
+        match self {
+            Heuristique::PatternDatabase(partition, db)
+                if db.as_ref().is_none_or(|db| db.goal() != goal.grid()) =>
+            {
+                *db = Some(Arc::new(PatternDatabase::load_or_build(
+                    &goal.grid(),
+                    partition,
+                )?))
+            }
+            Heuristique::WalkingDistance(wd) => *wd = Some(Arc::new(WalkingDistance::new(goal))),
+            Heuristique::Perfect(table)
+                if table
+                    .as_ref()
+                    .is_none_or(|table| table.goal() != goal.grid()) =>
+            {
+                *table = Some(Arc::new(PerfectTable::load_or_build(&goal.grid())?))
             }
+            _ => {}
         }
+        Ok(())
     }
 
-    pub fn process_h(&self, goal: &[Vec<i64>], grid: &[Vec<i64>]) -> Vec<Vec<f64>> {
+    /*
+     * The same heuristique without a table tied to one goal: Manhattan
+     * stands in for the pattern database and the perfect table
+     */
+    pub fn without_table(&self) -> Heuristique {
+        match self {
+            Heuristique::PatternDatabase(_, _) | Heuristique::Perfect(_) => Heuristique::Manhattan,
+            heuristique => heuristique.clone(),
+        }
+    }
+
+    /*
+     * Heuristiques valuing the grid as a whole rather than tile by tile,
+     * which cannot be weighted by position
+     */
+    pub fn whole_grid(&self) -> bool {
+        matches!(
+            self,
+            Heuristique::PatternDatabase(_, _)
+                | Heuristique::WalkingDistance(_)
+                | Heuristique::Perfect(_)
+        )
+    }
+
+    /*
+     * Heuristiques which never overestimate the number of moves left: the
+     * Hamming one counts the tiles in place
+     */
+    pub fn admissible(&self) -> bool {
+        !matches!(self, Heuristique::Hamming)
+    }
+
+    pub fn process_h(&self, grid: &Board, goal: &Board) -> Vec<Vec<f64>> {
         match &self {
             Heuristique::Hamming => Self::process_hamming(grid, goal),
             Heuristique::Manhattan => Self::process_manhattan(grid, goal),
             Heuristique::LinearConflict => Self::process_linearconflict(grid, goal),
+            Heuristique::PatternDatabase(_, db) => Self::process_whole(
+                grid,
+                db.as_ref()
+                    .expect("pattern database used before Strategy::init")
+                    .value(grid),
+            ),
+            Heuristique::WalkingDistance(wd) => Self::process_whole(
+                grid,
+                wd.as_ref()
+                    .expect("walking distance used before Strategy::init")
+                    .value(grid),
+            ),
+            Heuristique::Perfect(table) => Self::process_whole(
+                grid,
+                table
+                    .as_ref()
+                    .expect("perfect table used before Strategy::init")
+                    .value(grid),
+            ),
         }
     }
 
-    fn process_hamming(grid: &[Vec<i64>], goal: &[Vec<i64>]) -> Vec<Vec<f64>> {
-        grid.iter()
-            .enumerate()
-            .map(|(i, x)| {
-                x.iter()
-                    .enumerate()
-                    .map(|(j, &y)| {
-                        let (a, b) = find_nb(y, goal);
-                        if (a as usize) == i && (b as usize) == j {
-                            1f64
-                        } else {
-                            0f64
-                        }
-                    })
-                    .collect()
-            })
-            .collect()
+    /*
+     * Change of the per-cell values when the tile at `tile` slides into the
+     * empty space at `blank` (parent is the grid before the move): only
+     * the cells whose value can change are listed, homes being the goal's
+     */
+    pub fn process_delta(
+        &self,
+        parent: &Board,
+        grid: &Board,
+        homes: &Homes,
+        blank: (usize, usize),
+        tile: (usize, usize),
+    ) -> Vec<((usize, usize), f64)> {
+        let cells = |value: CellValue| {
+            [blank, tile]
+                .iter()
+                .map(|&(i, j)| {
+                    (
+                        (i, j),
+                        value(grid.at((i, j)), (i, j), homes)
+                            - value(parent.at((i, j)), (i, j), homes),
+                    )
+                })
+                .collect::<Vec<((usize, usize), f64)>>()
+        };
+        match &self {
+            Heuristique::Hamming => cells(Self::hamming_cell),
+            Heuristique::Manhattan => cells(Self::manhattan_cell),
+            Heuristique::LinearConflict => {
+                let mut res = cells(Self::manhattan_cell);
+                let mut rows = vec![blank.0, tile.0];
+                rows.dedup();
+                let mut columns = vec![blank.1, tile.1];
+                columns.dedup();
+                for &i in rows.iter() {
+                    res.extend(
+                        row_conflicts(parent, homes, i)
+                            .into_iter()
+                            .map(|j| ((i, j), -2f64)),
+                    );
+                    res.extend(
+                        row_conflicts(grid, homes, i)
+                            .into_iter()
+                            .map(|j| ((i, j), 2f64)),
+                    );
+                }
+                for &j in columns.iter() {
+                    res.extend(
+                        column_conflicts(parent, homes, j)
+                            .into_iter()
+                            .map(|i| ((i, j), -2f64)),
+                    );
+                    res.extend(
+                        column_conflicts(grid, homes, j)
+                            .into_iter()
+                            .map(|i| ((i, j), 2f64)),
+                    );
+                }
+                res
+            }
+            Heuristique::PatternDatabase(_, db) => vec![(
+                (0, 0),
+                db.as_ref()
+                    .expect("pattern database used before Strategy::init")
+                    .value_delta(parent, blank, tile),
+            )],
+            Heuristique::WalkingDistance(wd) => vec![(
+                (0, 0),
+                wd.as_ref()
+                    .expect("walking distance used before Strategy::init")
+                    .value_delta(parent, blank, tile),
+            )],
+            Heuristique::Perfect(table) => {
+                let table = table
+                    .as_ref()
+                    .expect("perfect table used before Strategy::init");
+                vec![((0, 0), table.value(grid) - table.value(parent))]
+            }
+        }
     }
 
-    fn process_manhattan(grid: &[Vec<i64>], goal: &[Vec<i64>]) -> Vec<Vec<f64>> {
-        grid.iter()
-            .enumerate()
-            .map(|(i, x)| {
-                x.iter()
-                    .enumerate()
-                    .map(|(j, &y)| {
-                        let (a, b) = find_nb(y, goal);
-                        if y != 0 && y == goal[a as usize][b as usize] {
-                            (i as f64 - a as f64).abs() + (j as f64 - b as f64).abs()
-                        } else {
-                            0f64
-                        }
-                    })
-                    .collect()
-            })
-            .collect()
+    fn hamming_cell(tile: i64, cell: (usize, usize), homes: &Homes) -> f64 {
+        if homes.get(tile) == Some(cell) {
+            1f64
+        } else {
+            0f64
+        }
+    }
+
+    fn manhattan_cell(tile: i64, (i, j): (usize, usize), homes: &Homes) -> f64 {
+        match homes.get(tile) {
+            Some((a, b)) if tile != 0 => (i as f64 - a as f64).abs() + (j as f64 - b as f64).abs(),
+            _ => 0f64,
+        }
     }
 
-    fn process_linearconflict(grid: &[Vec<i64>], goal: &[Vec<i64>]) -> Vec<Vec<f64>> {
-        let tmp_vec = Self::process_manhattan(grid, goal);
-        grid.iter()
-            .enumerate()
-            .map(|(i, x)| {
-                x.iter()
-                    .enumerate()
-                    .map(|(j, &y)| {
-                        let (a, b) = find_nb(y, goal);
-                        if y != 0 && (i == a as usize) ^ (j == b as usize) {
-                            if (i as f64 - a as f64).abs() as f64
-                                + (j as f64 - b as f64).abs() as f64
-                                != 1f64
-                                && goal[a as usize][b as usize] != 0
-                            {
-                                2f64 + tmp_vec[i][j]
-                            } else {
-                                0f64 + tmp_vec[i][j]
-                            }
-                        } else {
-                            0f64 + tmp_vec[i][j]
-                        }
-                    })
+    fn process_cells(grid: &Board, goal: &Board, value: CellValue) -> Vec<Vec<f64>> {
+        let homes = Homes::new(goal);
+        (0..grid.rows())
+            .map(|i| {
+                (0..grid.cols())
+                    .map(|j| value(grid.at((i, j)), (i, j), &homes))
                     .collect()
             })
             .collect()
     }
+
+    /*
+     * A value of the whole grid, kept in its first cell
+     */
+    fn process_whole(grid: &Board, value: f64) -> Vec<Vec<f64>> {
+        let mut res = vec![vec![0f64; grid.cols()]; grid.rows()];
+        res[0][0] = value;
+        res
+    }
+
+    fn process_hamming(grid: &Board, goal: &Board) -> Vec<Vec<f64>> {
+        Self::process_cells(grid, goal, Self::hamming_cell)
+    }
+
+    fn process_manhattan(grid: &Board, goal: &Board) -> Vec<Vec<f64>> {
+        Self::process_cells(grid, goal, Self::manhattan_cell)
+    }
+
+    /*
+     * Manhattan distance plus two moves for every tile that has to leave
+     * its line: in each row (column), among the tiles already in their goal
+     * row (column), the ones kept in place form the longest sequence whose
+     * goal order matches their current order, the others are in conflict
+     */
+    fn process_linearconflict(grid: &Board, goal: &Board) -> Vec<Vec<f64>> {
+        let mut res = Self::process_manhattan(grid, goal);
+        let homes = &Homes::new(goal);
+        let rows = (0..grid.rows()).flat_map(|i| {
+            row_conflicts(grid, homes, i)
+                .into_iter()
+                .map(move |j| (i, j))
+        });
+        let columns = (0..grid.cols()).flat_map(|j| {
+            column_conflicts(grid, homes, j)
+                .into_iter()
+                .map(move |i| (i, j))
+        });
+        for (i, j) in rows.chain(columns) {
+            res[i][j] += 2f64;
+        }
+        res
+    }
+}
+
+/*
+ * Columns of the tiles of row i which have to leave it
+ */
+fn row_conflicts(grid: &Board, homes: &Homes, i: usize) -> Vec<usize> {
+    let mut row = vec![];
+    let mut goals = vec![];
+    for j in 0..grid.cols() {
+        let y = grid.at((i, j));
+        match homes.get(y) {
+            Some((a, b)) if y != 0 && a == i => {
+                row.push(j);
+                goals.push(b);
+            }
+            _ => {}
+        }
+    }
+    row.into_iter()
+        .zip(conflicting(&goals))
+        .filter(|&(_, conflict)| conflict)
+        .map(|(j, _)| j)
+        .collect()
+}
+
+/*
+ * Rows of the tiles of column j which have to leave it
+ */
+fn column_conflicts(grid: &Board, homes: &Homes, j: usize) -> Vec<usize> {
+    let mut column = vec![];
+    let mut goals = vec![];
+    for i in 0..grid.rows() {
+        let y = grid.at((i, j));
+        match homes.get(y) {
+            Some((a, b)) if y != 0 && b == j => {
+                column.push(i);
+                goals.push(a);
+            }
+            _ => {}
+        }
+    }
+    column
+        .into_iter()
+        .zip(conflicting(&goals))
+        .filter(|&(_, conflict)| conflict)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/*
+ * Smallest set of tiles to take out of a line so that the others are in
+ * goal order: everything outside one longest increasing subsequence
+ */
+fn conflicting(goals: &[usize]) -> Vec<bool> {
+    let n = goals.len();
+    let mut length = vec![1usize; n];
+    let mut previous: Vec<Option<usize>> = vec![None; n];
+    for i in 0..n {
+        for j in 0..i {
+            if goals[j] < goals[i] && length[j] + 1 > length[i] {
+                length[i] = length[j] + 1;
+                previous[i] = Some(j);
+            }
+        }
+    }
+    let mut res = vec![true; n];
+    let mut cur = (0..n).max_by_key(|&i| length[i]);
+    while let Some(i) = cur {
+        res[i] = false;
+        cur = previous[i];
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::utils::*;
+    use super::*;
+    use std::collections::{HashMap, VecDeque};
+
+    /*
+     * Optimal distance to the goal of every reachable 3x3 state
+     */
+    fn solve_all(goal: &[Vec<i64>]) -> HashMap<Vec<Vec<i64>>, usize> {
+        let mut distances = HashMap::new();
+        let mut queue = VecDeque::new();
+        distances.insert(goal.to_vec(), 0);
+        queue.push_back(goal.to_vec());
+        while let Some(grid) = queue.pop_front() {
+            let d = distances[&grid];
+            let (x, y) = find_nb(0, &grid);
+            for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)].iter() {
+                let (a, b) = (x + dx, y + dy);
+                if a < 0 || b < 0 || a >= 3 || b >= 3 {
+                    continue;
+                }
+                let mut next = grid.clone();
+                next[x as usize][y as usize] = next[a as usize][b as usize];
+                next[a as usize][b as usize] = 0;
+                if !distances.contains_key(&next) {
+                    distances.insert(next.clone(), d + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+        distances
+    }
+
+    fn total(matrix: Vec<Vec<f64>>) -> f64 {
+        matrix.iter().flatten().sum()
+    }
+
+    #[test]
+    fn test_conflicting() {
+        assert_eq!(vec![false, false, false], conflicting(&[0, 1, 2]));
+        assert_eq!(vec![true, true, false], conflicting(&[2, 1, 0]));
+        assert_eq!(vec![false, true, false], conflicting(&[0, 2, 1]));
+    }
+
+    #[test]
+    fn test_linearconflict_row() {
+        let goal = &[vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 0]];
+        let grid = &[vec![3, 2, 1], vec![4, 5, 6], vec![7, 8, 0]];
+        let (grid, goal) = (Board::new(grid).unwrap(), Board::new(goal).unwrap());
+        // Manhattan 4, two tiles have to leave the first row
+        assert_eq!(
+            8f64,
+            total(Heuristique::process_linearconflict(&grid, &goal))
+        );
+    }
+
+    #[test]
+    fn test_linearconflict_admissible() {
+        let goals = [
+            vec![vec![1, 2, 3], vec![8, 0, 4], vec![7, 6, 5]],
+            vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 0]],
+        ];
+        for goal in goals.iter() {
+            let distances = solve_all(goal);
+            assert_eq!(181_440, distances.len());
+            let goal = Board::new(goal).unwrap();
+            for (grid, &d) in distances.iter() {
+                let board = Board::new(grid).unwrap();
+                let manhattan = total(Heuristique::process_manhattan(&board, &goal));
+                let h = total(Heuristique::process_linearconflict(&board, &goal));
+                assert!(manhattan <= h, "{:?}", grid);
+                assert!(h <= d as f64, "{:?}: h = {} > {}", grid, h, d);
+            }
+        }
+    }
 }
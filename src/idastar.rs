@@ -0,0 +1,95 @@
+use super::node::*;
+use super::{NPuzzle, Outcome, Stop};
+use std::cmp;
+use std::f64;
+use std::sync::Arc;
+
+enum Search {
+    Found(Arc<Node>),
+    Bound(f64),
+    GaveUp(Stop),
+}
+
+impl NPuzzle {
+    /*
+     * Iterative deepening A*: depth-first passes bounded by f = g + h,
+     * the next threshold being the smallest f which exceeded the current one
+     */
+    pub(crate) fn run_idastar(&mut self) -> Outcome {
+        let root = self.open_list.pop().unwrap();
+        let mut closest = root.clone();
+        let mut threshold = root.f;
+        let mut iterations: u64 = 0;
+
+        let outcome = loop {
+            self.stats.thresholds.push(threshold);
+            let pass = self.stats.thresholds.len();
+            self.trace(|| format!("PASS {} - THRESHOLD : {}", pass, threshold));
+            match self.ida_search(&root, threshold, &mut iterations, &mut closest) {
+                Search::Found(node) => break Outcome::Solved(node),
+                Search::Bound(next) => threshold = next,
+                Search::GaveUp(stop) => break Outcome::GaveUp(closest.clone(), stop),
+            }
+        };
+        self.stats.iterations = iterations;
+        outcome
+    }
+
+    fn ida_search(
+        &mut self,
+        node: &Arc<Node>,
+        threshold: f64,
+        iterations: &mut u64,
+        closest: &mut Arc<Node>,
+    ) -> Search {
+        if node.f > threshold {
+            return Search::Bound(node.f);
+        }
+        if node.grid == self.goal {
+            return Search::Found(node.clone());
+        }
+        *iterations += 1;
+        if node.h < closest.h {
+            *closest = node.clone();
+        }
+        if let Some(stop) = self.stop(*iterations) {
+            return Search::GaveUp(stop);
+        }
+        // Only the current path is kept in memory
+        self.stats.max_states = cmp::max(self.stats.max_states, node.g as usize + 1);
+        self.trace(|| format!("ITERATION: {}\nCURRENT : {:?}", iterations, node));
+
+        let grandparent = node.parent.as_ref().map(|p| &p.grid);
+        let mut children: Vec<Arc<Node>> = self
+            .expand(node)
+            .into_iter()
+            .filter(|child| Some(&child.grid) != grandparent)
+            .collect();
+        children.sort_by(|a, b| a.f.partial_cmp(&b.f).unwrap());
+
+        let mut min = f64::INFINITY;
+        for child in children.iter() {
+            match self.ida_search(child, threshold, iterations, closest) {
+                Search::Bound(bound) => min = min.min(bound),
+                found_or_stopped => return found_or_stopped,
+            }
+        }
+        Search::Bound(min)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::heuristique::*;
+    use crate::tests::*;
+    #[test]
+    fn test_idastar_optimal() {
+        for (file, goal) in SAMPLES.iter() {
+            let cost = |algorithm| solve(file, algorithm, Heuristique::Manhattan, goal, 1.0).cost;
+            assert_eq!(cost("astar"), cost("idastar"), "{}", file);
+            let solution = solve(file, "idastar", Heuristique::Manhattan, goal, 1.0);
+            // The last pass is bounded by the cost of the path it found
+            assert_eq!(Some(&solution.cost), solution.stats.thresholds.last());
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use super::diagnosis::*;
+use super::error::*;
+use super::file::*;
+use super::goal::*;
+use super::replay::*;
+use super::solution::*;
+use super::NPuzzle;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/*
+ * How puzzles are read and results written
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl Format {
+    pub fn parse(s: String) -> Result<Format, NPuzzleError> {
+        Ok(match &s.to_lowercase() as &str {
+            "text" => Format::Text,
+            "json" => Format::Json,
+            _ => return Err(NPuzzleError::UnknownFormat(s)),
+        })
+    }
+}
+
+/*
+ * "size": 3 or [rows, cols]
+ */
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Size {
+    Square(i64),
+    Rectangle(i64, i64),
+}
+
+/*
+ * "goal": "snail", "std" or the tiles
+ */
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GoalInput {
+    Name(String),
+    Tiles(Vec<Vec<i64>>),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Input {
+    size: Option<Size>,
+    tiles: Vec<Vec<i64>>,
+    goal: Option<GoalInput>,
+}
+
+/*
+ * {"size": 3, "tiles": [[...]], "goal": ...}: the size defaults to the
+ * one of the tiles, the goal to the one given on the command line
+ */
+pub fn parse_json(arg: &str) -> Result<(ParsedMap, Option<Goal>), NPuzzleError> {
+    let input: Input = serde_json::from_str(arg).map_err(|e| NPuzzleError::Json(e.to_string()))?;
+    let tiles = input.tiles;
+    let found = (
+        tiles.len() as i64,
+        tiles.first().map_or(0, |row| row.len()) as i64,
+    );
+    let size = match input.size {
+        Some(Size::Square(n)) => (n, n),
+        Some(Size::Rectangle(rows, cols)) => (rows, cols),
+        None => found,
+    };
+    if size.0 <= 0 || size.1 <= 0 {
+        return Err(NPuzzleError::Json(format!("invalid size {:?}", size)));
+    }
+    if found.0 != size.0 || tiles.iter().any(|row| row.len() as i64 != size.1) {
+        return Err(NPuzzleError::Json(format!(
+            "the tiles do not form a {}x{} grid",
+            size.0, size.1
+        )));
+    }
+    if let Some(&tile) = tiles.iter().flatten().find(|&&t| t < 0) {
+        return Err(NPuzzleError::Json(format!("tile {} is negative", tile)));
+    }
+    let mut seen = HashSet::new();
+    let mut duplicated: Vec<i64> = tiles
+        .iter()
+        .flatten()
+        .cloned()
+        .filter(|&t| !seen.insert(t))
+        .collect();
+    duplicated.sort();
+    duplicated.dedup();
+    let missing: Vec<i64> = (0..size.0 * size.1).filter(|t| !seen.contains(t)).collect();
+    if !duplicated.is_empty() || !missing.is_empty() {
+        return Err(NPuzzleError::Json(format!(
+            "tiles have to be numbered from 0 to {} (duplicated: {:?}; missing: {:?})",
+            size.0 * size.1 - 1,
+            duplicated,
+            missing
+        )));
+    }
+    let goal = match input.goal {
+        None => None,
+        Some(GoalInput::Tiles(tiles)) => Some(Goal::Tiles(tiles)),
+        Some(GoalInput::Name(name)) => match &name.to_lowercase() as &str {
+            "snail" | "std" | "standard" => Some(Goal::parse(name, "".to_string())),
+            _ => return Err(NPuzzleError::Json(format!("unknown goal {}", name))),
+        },
+    };
+    Ok(((size, tiles), goal))
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    size: (i64, i64),
+    initial: Vec<Vec<i64>>,
+    goal: Vec<Vec<i64>>,
+    /* Tiles swapped by --fix */
+    fixed: Option<(i64, i64)>,
+    solved: bool,
+    stop: Option<String>,
+    convention: &'a str,
+    moves: String,
+    tiles: Vec<i64>,
+    grids: &'a [Vec<Vec<i64>>],
+    move_count: f64,
+    bound: Option<f64>,
+    iterations: u64,
+    max_states: usize,
+    /* IDA* only */
+    thresholds: &'a [f64],
+    /* Seconds */
+    elapsed: f64,
+}
+
+/*
+ * Machine-readable result of a run
+ */
+pub fn report(puzzle: &NPuzzle, solution: &Solution, convention: Convention) -> String {
+    let report = Report {
+        size: puzzle.size,
+        initial: puzzle.initial.grid(),
+        goal: puzzle.goal.grid(),
+        fixed: puzzle.fixed.map(|(_, a, b)| (a, b)),
+        solved: solution.solved,
+        stop: solution.stop.map(|stop| stop.to_string()),
+        convention: match convention {
+            Convention::Blank => "blank",
+            Convention::Tile => "tile",
+        },
+        moves: solution.notation(convention),
+        tiles: solution.tiles(),
+        grids: &solution.path,
+        move_count: solution.cost,
+        bound: solution.bound,
+        iterations: solution.stats.iterations,
+        max_states: solution.stats.max_states,
+        thresholds: &solution.stats.thresholds,
+        elapsed: solution.stats.elapsed.as_secs_f64(),
+    };
+    serde_json::to_string(&report).unwrap()
+}
+
+/*
+ * Whether a puzzle can reach its goal, and why not
+ */
+pub fn check(
+    size: (i64, i64),
+    initial: &[Vec<i64>],
+    goal: &[Vec<i64>],
+    diagnosis: &Diagnosis,
+) -> String {
+    let solvable = diagnosis.solvability.solvable;
+    serde_json::json!({
+        "size": size,
+        "initial": initial,
+        "goal": goal,
+        "solvable": solvable,
+        "solvability": diagnosis.solvability.to_string(),
+        "diagnosis": if solvable { None } else { Some(diagnosis.to_string()) },
+    })
+    .to_string()
+}
+
+/*
+ * What a move string does: the number of moves played, the first one
+ * leaving the board, whether the goal is reached and, when asked, the
+ * optimal number of moves
+ */
+pub fn verify(replay: &Replay, optimal: Option<f64>) -> String {
+    serde_json::json!({
+        "moves": replay.path.len() - 1,
+        "illegal": replay.illegal.map(|(n, d)| (n + 1, d.to_string())),
+        "solved": replay.solved,
+        "last": replay.path.last().map(|board| board.grid()),
+        "optimal": optimal,
+    })
+    .to_string()
+}
+
+/*
+ * {"error": "..."}
+ */
+pub fn error(err: &NPuzzleError) -> String {
+    match err {
+        NPuzzleError::Unsolvable(diagnosis) => serde_json::json!({
+            "error": err.to_string(),
+            "diagnosis": diagnosis.to_string(),
+        }),
+        _ => serde_json::json!({ "error": err.to_string() }),
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_parse_json() {
+        let ((size, tiles), goal) =
+            parse_json(r#"{"size": [2, 3], "tiles": [[1, 2, 3], [4, 5, 0]], "goal": "std"}"#)
+                .unwrap();
+        assert_eq!((2, 3), size);
+        assert_eq!(vec![vec![1, 2, 3], vec![4, 5, 0]], tiles);
+        assert!(matches!(goal, Some(Goal::Standard)));
+        let ((size, _), goal) =
+            parse_json(r#"{"tiles": [[1, 2], [3, 0]], "goal": [[0, 1], [2, 3]]}"#).unwrap();
+        assert_eq!((2, 2), size);
+        assert!(matches!(goal, Some(Goal::Tiles(_))));
+        assert!(parse_json(r#"{"size": 2, "tiles": [[1, 2], [3, 3]]}"#).is_err());
+        assert!(parse_json(r#"{"size": 3, "tiles": [[1, 2], [3, 0]]}"#).is_err());
+        assert!(parse_json(r#"{"size": 2, "tiles": [[1, 2], [3, 0]], "extra": 1}"#).is_err());
+    }
+}
@@ -1,194 +1,382 @@
 use rayon::prelude::*;
 use std::cmp;
 use std::collections::BinaryHeap;
-use std::error::Error;
-use std::process;
+use std::fmt;
+use std::iter;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 pub mod algorithm;
+mod ara;
+pub mod batch;
+mod bidirectional;
+pub mod board;
+pub mod cli;
+pub mod config;
+pub mod diagnosis;
+pub mod error;
 pub mod file;
+pub mod generate;
 pub mod goal;
+pub mod hardest;
 pub mod heuristique;
+mod idastar;
+pub mod json;
+pub mod list;
 pub mod node;
+pub mod pattern;
+pub mod perfect;
+pub mod replay;
+pub mod solution;
 pub mod strategy;
 pub mod utils;
+pub mod walking;
 
 use algorithm::*;
+use board::*;
+use diagnosis::*;
+use error::*;
 use file::*;
 use goal::*;
 use heuristique::*;
+use list::*;
 use node::*;
+use solution::*;
 use strategy::*;
 use utils::*;
 
+/*
+ * Why a search stopped before reaching the goal
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stop {
+    Iterations,
+    Timeout,
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stop::Iterations => write!(f, "maximum number of iterations reached"),
+            Stop::Timeout => write!(f, "time limit reached"),
+        }
+    }
+}
+
+/*
+ * Result of a search: the goal node, the last goal node found when the
+ * search was stopped while improving it, or the node closest to the goal
+ * (lowest h) when the search gave up
+ */
+#[derive(Debug)]
+enum Outcome {
+    Solved(Arc<Node>),
+    Stopped(Arc<Node>, Stop),
+    GaveUp(Arc<Node>, Stop),
+}
+
 #[derive(Debug)]
 pub struct NPuzzle {
-    pub size: i64,
-    pub goal: Vec<Vec<i64>>,
+    pub size: (i64, i64),
+    pub initial: Board,
+    pub goal: Board,
+    pub solvability: Solvability,
+    /* Swap applied to make an unsolvable initial state solvable */
+    pub fixed: Option<(Swap, i64, i64)>,
     pub algorithm: Algorithm,
     pub strategy: Strategy,
-    pub open_list: BinaryHeap<Arc<Node>>,
-    pub close_list: BinaryHeap<Arc<Node>>,
-    pub max_state: usize,
+    pub weight: f64,
+    pub open_list: OpenList,
+    pub close_list: ClosedSet,
+    pub stats: Stats,
     pub max_iteration: u64,
-    pub debug: bool,
+    pub timeout: Option<Duration>,
+    /* Receives a description of every expanded node */
+    pub trace: Option<fn(&str)>,
+    /* Receives every path ARA* publishes, numbered from 1 */
+    pub on_improvement: Option<fn(usize, &Improvement)>,
+    /* Nodes popped and expanded together at each epoch of a best-first search */
     pub thread: usize,
+    homes: Homes,
+    deadline: Option<Instant>,
+    /* Smallest f left to expand when a best-first search stopped with a path */
+    lower_bound: Option<f64>,
 }
 
 impl NPuzzle {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        arg: String,
+        (size, mut initial): ParsedMap,
         heuristique: Heuristique,
         algorithm: Algorithm,
         strategy: String,
         goal: Goal,
+        weight: f64,
         max_iteration: u64,
-        debug: bool,
+        timeout: Option<Duration>,
+        fix: bool,
+        trace: Option<fn(&str)>,
         thread: usize,
-    ) -> Result<NPuzzle, Box<dyn Error>> {
-        let (size, initial) = parse_file(arg)?;
-        println!("SIZE : {:?}", size);
-        println!("INITIAL : {:?}", initial);
-        let goal = goal.generate(size, &initial);
-        println!("GOAL : {:?}", goal);
-        if !solvable(&initial, &goal) {
-            println!("Unsolvable puzzle");
-            process::exit(1);
+    ) -> Result<NPuzzle, NPuzzleError> {
+        let goal = goal.generate(size, &initial)?;
+        let solvability = solvability(&initial, &goal);
+        let mut fixed = None;
+        if !solvability.solvable {
+            let diagnosis = Diagnosis::new(&initial, &goal);
+            match diagnosis.fix {
+                Some((swap, a, b)) if fix => {
+                    initial = swap.apply(&initial);
+                    fixed = Some((swap, a, b));
+                }
+                _ => return Err(NPuzzleError::Unsolvable(Box::new(diagnosis))),
+            }
         }
-        let mut strategy = Strategy::parse(strategy, heuristique);
-        strategy.init(&goal);
-        println!("Strategy: {:?}", strategy);
-        let mut open_list: BinaryHeap<Arc<Node>> = BinaryHeap::new();
+        let board = |grid: &[Vec<i64>]| Board::new(grid).ok_or(NPuzzleError::BoardTooLarge);
+        let (initial, goal) = (board(&initial)?, board(&goal)?);
+        let mut strategy = Strategy::parse(strategy, heuristique)?;
+        strategy.init(&goal)?;
+        let mut open_list = OpenList::new();
         open_list.push(Arc::new(Node::new(
-            initial, None, &goal, &algorithm, &strategy,
+            initial.clone(),
+            None,
+            &goal,
+            &algorithm,
+            &strategy,
+            weight,
         )));
         Ok(NPuzzle {
             size,
-            goal: goal.clone(),
+            initial,
+            homes: Homes::new(&goal),
+            goal,
+            solvability,
+            fixed,
             algorithm,
             strategy,
+            weight,
             open_list,
-            close_list: BinaryHeap::new(),
-            max_state: 0,
+            close_list: ClosedSet::new(),
+            stats: Stats::default(),
             max_iteration,
-            debug,
-            thread,
+            timeout,
+            trace,
+            on_improvement: None,
+            thread: thread.max(1),
+            deadline: None,
+            lower_bound: None,
         })
     }
 
     /*
      * Main loop which runs the algorithm
      */
-    pub fn run(&mut self) {
-        println!("RUN !");
+    pub fn run(&mut self) -> Result<Solution, NPuzzleError> {
+        let start = Instant::now();
+        self.deadline = self.timeout.map(|t| start + t);
+        let outcome = match self.algorithm {
+            Algorithm::IdaStar => self.run_idastar(),
+            Algorithm::Bidirectional => self.run_bidirectional()?,
+            Algorithm::AraStar => self.run_arastar(),
+            _ => self.run_best_first(),
+        };
+        self.stats.elapsed = start.elapsed();
+        let bound = match (self.algorithm, &outcome) {
+            (Algorithm::AraStar, _) => self
+                .stats
+                .improvements
+                .last()
+                .map(|last| last.bound)
+                .filter(|&bound| bound > 1.0),
+            // The optimal cost is at least the smallest f over the weight
+            (_, Outcome::Stopped(node, _)) => self
+                .lower_bound
+                .filter(|&lower| lower > 0.0)
+                .map(|lower| self.weight * node.g / lower),
+            _ if self.weight > 1.0 => Some(self.weight),
+            _ => None,
+        };
+        let stats = self.stats.clone();
+        Ok(match outcome {
+            Outcome::Solved(node) => Solution::new(&node, true, None, bound, stats),
+            Outcome::Stopped(node, stop) => Solution::new(&node, true, Some(stop), bound, stats),
+            Outcome::GaveUp(node, stop) => Solution::new(&node, false, Some(stop), None, stats),
+        })
+    }
+
+    /*
+     * Hand a line to the trace callback, if any, building it only then
+     */
+    fn trace(&self, line: impl FnOnce() -> String) {
+        if let Some(trace) = self.trace {
+            trace(&line());
+        }
+    }
+
+    /*
+     * Check the iteration budget and the deadline
+     */
+    fn stop(&self, iterations: u64) -> Option<Stop> {
+        if iterations >= self.max_iteration {
+            Some(Stop::Iterations)
+        } else if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            Some(Stop::Timeout)
+        } else {
+            None
+        }
+    }
+
+    /*
+     * Best-first search popping up to `thread` nodes per epoch and
+     * expanding them in parallel.
+     * Popping several nodes at once must not cost A* its optimality: a goal
+     * popped with other nodes may have a larger g than a path through one of
+     * them. A goal is therefore only kept as the incumbent, and the search
+     * ends once its g is no larger than the smallest f still to expand,
+     * among the other popped nodes and the open list. With an admissible h,
+     * f = g + h bounds every path through a node from below, so no shorter
+     * path is left (with a weight w, the path stays within w times the
+     * optimal one, as with a single pop). Greedy and B* keep the first goal.
+     * Stopped before that, the search returns the incumbent, within g over
+     * that smallest f (times w) of the optimal cost.
+     */
+    fn run_best_first(&mut self) -> Outcome {
         let mut epochs: u64 = 0;
         let mut next: Vec<Arc<Node>> = vec![self.open_list.pop().unwrap()];
-        let zero = Arc::new(Node {
-            grid: vec![],
-            f: 100_000f64,
-            h: 100_000f64,
-            g: 100_000f64,
-            parent: None,
-        });
-
-        let solved = loop {
+        let mut closest = next[0].clone();
+        let mut incumbent: Option<Arc<Node>> = None;
+
+        let outcome = loop {
             epochs += 1;
-            let currents = next;
+            let (goals, currents): (Vec<Arc<Node>>, Vec<Arc<Node>>) =
+                next.into_iter().partition(|x| x.grid == self.goal);
 
-            if currents.iter().any(|x| x.grid == self.goal) {
-                break currents
-                    .iter()
-                    .fold(zero, |a, b| std::cmp::max(a, b.clone()));
+            for goal in goals {
+                if incumbent.as_ref().is_none_or(|best| goal.g < best.g) {
+                    incumbent = Some(goal);
+                }
             }
-
-            if self.debug {
-                println!("EPOCH: {}", epochs);
-                println!("CURRENTS : {:?}", currents);
+            let bound = currents
+                .iter()
+                .map(|x| x.f)
+                .chain(self.open_list.min_f())
+                .fold(f64::INFINITY, f64::min);
+            if let Some(best) = &incumbent {
+                let proven = match self.algorithm {
+                    Algorithm::Greedy | Algorithm::BStar => true,
+                    _ => best.g <= bound,
+                };
+                if proven {
+                    break Outcome::Solved(best.clone());
+                }
+            }
+            for current in currents.iter() {
+                if current.h < closest.h {
+                    closest = current.clone();
+                }
             }
+            if let Some(stop) = self.stop(epochs) {
+                // A path not proven optimal yet is still better than none
+                break match incumbent {
+                    Some(best) => {
+                        self.lower_bound = Some(bound);
+                        Outcome::Stopped(best, stop)
+                    }
+                    None => Outcome::GaveUp(closest, stop),
+                };
+            }
+
+            self.trace(|| format!("EPOCH: {}\nCURRENTS : {:?}", epochs, currents));
 
-            let mut swaps: BinaryHeap<Arc<Node>> = currents
+            let swaps: Vec<Arc<Node>> = currents
                 .par_iter()
-                .map(|current| self.generate_swaps(find_nb(0, &current.grid), current))
+                .map(|current| self.generate_swaps(current))
                 .flatten()
                 .collect();
 
-            self.close_list.extend(currents);
-            self.open_list = self
-                .open_list
-                .iter()
-                .cloned()
-                .filter(|x| !swaps.iter().any(|y| y == x))
+            for current in currents {
+                self.close_list.insert(current);
+            }
+            let mut swaps: BinaryHeap<Candidate> = swaps
+                .into_iter()
+                .filter(|swap| self.open_list.push(swap.clone()))
+                .map(|swap| Candidate::new(swap.f, swap))
                 .collect();
 
-            let it: _ = (0..self.thread).into_iter();
+            let it = 0..self.thread;
             next = match self.algorithm {
-                Algorithm::AStar | Algorithm::BStar => {
-                    self.open_list.extend(swaps);
-                    it.map(|_| self.open_list.pop())
-                        .filter_map(Option::Some)
-                        .map(|x| x.unwrap())
-                        .collect()
-                }
-                Algorithm::Greedy => {
-                    let res: _ = it
-                        .map(|_| swaps.pop().unwrap_or_else(|| self.open_list.pop().unwrap()))
-                        .collect();
-                    self.open_list.extend(swaps);
-                    res
-                }
+                // Children first, skipping the ones a cheaper path replaced
+                Algorithm::Greedy => it
+                    .filter_map(|_| {
+                        iter::from_fn(|| swaps.pop().map(|swap| swap.node))
+                            .find(|swap| self.open_list.remove(swap))
+                            .or_else(|| self.open_list.pop())
+                    })
+                    .collect(),
+                _ => it.filter_map(|_| self.open_list.pop()).collect(),
             };
 
-            self.max_state = cmp::max(self.max_state, self.open_list.len());
+            self.stats.max_states = cmp::max(self.stats.max_states, self.open_list.len());
         };
-        // Display of the solved puzzle
-        println!("RESOLVED :");
-        let g = solved.g;
-        Self::display(&Some(solved));
-        println!("Number of moves: {}", g);
-        println!("Number of iterations : {}", epochs);
-        println!("Complexity Size (Max States): {}", self.max_state);
-    }
-
-    fn display(cur: &Option<Arc<Node>>) {
-        if cur.is_some() {
-            let _ = cur.as_ref().map(|node| {
-                Self::display(&node.parent);
-                println!("{}", node);
-            });
-        }
+        self.stats.iterations = epochs;
+        outcome
     }
 
-    fn generate_swaps(&self, pos: (i32, i32), parent: &Arc<Node>) -> Vec<Arc<Node>> {
-        let current_grid = parent.grid.clone();
-        let goal = self.goal.clone();
+    fn generate_swaps(&self, parent: &Arc<Node>) -> Vec<Arc<Node>> {
+        self.expand(parent)
+            .into_par_iter()
+            .filter(|swap| !self.close_list.contains_better(swap))
+            .collect()
+    }
+
+    /*
+     * Every child reachable by sliding one tile into the empty space
+     */
+    fn expand(&self, parent: &Arc<Node>) -> Vec<Arc<Node>> {
+        self.expand_towards(parent, &self.homes, &self.strategy)
+    }
+
+    /*
+     * Same as expand, with the heuristic measured against another target
+     * given by the homes of its tiles
+     */
+    fn expand_towards(
+        &self,
+        parent: &Arc<Node>,
+        homes: &Homes,
+        strategy: &Strategy,
+    ) -> Vec<Arc<Node>> {
+        let width = parent.grid.cols();
+        let blank = parent.grid.blank();
+        let pos = (blank.0 as i32, blank.1 as i32);
 
         [(-1, 0), (0, 1), (1, 0), (0, -1)]
-            .par_iter()
+            .iter()
             .filter(|&(x, y)| {
                 pos.0 + x >= 0
                     && pos.1 + y >= 0
-                    && pos.0 + x < self.size as i32
-                    && pos.1 + y < self.size as i32
+                    && pos.0 + x < self.size.0 as i32
+                    && pos.1 + y < self.size.1 as i32
             })
             .map(|(x, y)| {
-                let mut swap = current_grid.clone();
-                swap[pos.0 as usize][pos.1 as usize] =
-                    swap[(pos.0 + x) as usize][(pos.1 + y) as usize];
-                swap[(pos.0 + x) as usize][(pos.1 + y) as usize] = 0;
-                Arc::new(Node::new(
+                let tile = ((pos.0 + x) as usize, (pos.1 + y) as usize);
+                let swap = parent.grid.slide(tile);
+                let h = strategy.process_delta(parent.h, &parent.grid, &swap, homes, (blank, tile));
+                let moved = swap.at(blank);
+                let (b, t) = (blank.0 * width + blank.1, tile.0 * width + tile.1);
+                let hash = parent.hash
+                    ^ zobrist_key(b, 0)
+                    ^ zobrist_key(t, moved)
+                    ^ zobrist_key(b, moved)
+                    ^ zobrist_key(t, 0);
+                Arc::new(Node::with_h(
                     swap,
+                    hash,
                     Some(parent.clone()),
-                    &goal,
+                    h,
                     &self.algorithm,
-                    &self.strategy,
+                    self.weight,
                 ))
             })
-            .filter(|swap| {
-                !self
-                    .close_list
-                    .iter()
-                    .any(|x: &Arc<Node>| x.grid == swap.grid && x.f <= swap.f)
-            })
             .collect()
     }
 }
@@ -200,8 +388,196 @@ mod tests {
     fn test_goals() {
         let goal: Goal = Goal::Snail;
         assert_eq!(
-            goal.generate(3, &vec![vec![3, 1, 5], vec![4, 2, 6], vec![0, 8, 7]]),
+            goal.generate((3, 3), &[vec![3, 1, 5], vec![4, 2, 6], vec![0, 8, 7]])
+                .unwrap(),
             vec![vec![1, 2, 3], vec![8, 0, 4], vec![7, 6, 5]]
         );
     }
+    #[test]
+    fn test_goals_rectangle() {
+        let initial = &[
+            vec![3, 1, 5, 9, 10],
+            vec![4, 2, 6, 11, 12],
+            vec![0, 8, 7, 13, 14],
+        ];
+        assert_eq!(
+            Goal::Snail.generate((3, 5), initial).unwrap(),
+            vec![
+                vec![1, 2, 3, 4, 5],
+                vec![12, 13, 14, 0, 6],
+                vec![11, 10, 9, 8, 7]
+            ]
+        );
+        assert_eq!(
+            Goal::Standard.generate((3, 5), initial).unwrap(),
+            vec![
+                vec![1, 2, 3, 4, 5],
+                vec![6, 7, 8, 9, 10],
+                vec![11, 12, 13, 14, 0]
+            ]
+        );
+        assert_eq!(
+            Goal::Snail
+                .generate((4, 2), &[vec![1, 2], vec![3, 4], vec![5, 6], vec![7, 0]])
+                .unwrap(),
+            vec![vec![1, 2], vec![0, 3], vec![7, 4], vec![6, 5]]
+        );
+    }
+    #[test]
+    fn test_timeout() {
+        let arg = "4\n0 12 9 13\n15 11 10 14\n3 7 2 5\n4 8 6 1\n".to_string();
+        let mut puzzle = NPuzzle::new(
+            parse_file(arg).unwrap(),
+            Heuristique::Manhattan,
+            Algorithm::AStar,
+            "std".to_string(),
+            Goal::Standard,
+            1.0,
+            u64::MAX,
+            Some(Duration::from_millis(20)),
+            false,
+            None,
+            1,
+        )
+        .unwrap();
+        let solution = puzzle.run().unwrap();
+        assert!(!solution.solved);
+        assert_eq!(Some(Stop::Timeout), solution.stop);
+        // The partial path starts from the initial grid
+        assert!(!solution.path.is_empty());
+        assert_eq!(puzzle.initial.grid(), solution.path[0]);
+    }
+    #[test]
+    fn test_multi_pop_optimal() {
+        let goal = |seed| {
+            generate::Generator::new(
+                (3, 3),
+                &Goal::Standard,
+                Heuristique::LinearConflict,
+                Some(seed),
+            )
+            .unwrap()
+        };
+        let table = perfect::PerfectTable::build(goal(0).goal()).unwrap();
+        // The first three overshoot when stopping at the first goal popped
+        for seed in [41, 46, 135, 1, 2, 3, 4, 5] {
+            let puzzle = goal(seed)
+                .generate(generate::Scramble::Shuffle, Some(true))
+                .unwrap();
+            let optimal = table.distance(&Board::new(&puzzle.tiles).unwrap());
+            for thread in [1, 4, 7, 16] {
+                let config = config::Config {
+                    heuristique: Heuristique::LinearConflict,
+                    goal: Goal::Standard,
+                    thread,
+                    ..Default::default()
+                };
+                let solution = config
+                    .puzzle(((3, 3), puzzle.tiles.clone()), None)
+                    .unwrap()
+                    .run()
+                    .unwrap();
+                assert_eq!(optimal.map(|d| d as f64), Some(solution.cost));
+            }
+        }
+    }
+    #[test]
+    fn test_multi_pop_stopped() {
+        let puzzle = generate::Generator::new(
+            (3, 3),
+            &Goal::Standard,
+            Heuristique::LinearConflict,
+            Some(41),
+        )
+        .unwrap()
+        .generate(generate::Scramble::Shuffle, Some(true))
+        .unwrap();
+        let run = |max_iteration| {
+            config::Config {
+                heuristique: Heuristique::LinearConflict,
+                goal: Goal::Standard,
+                max_iteration,
+                thread: 16,
+                ..Default::default()
+            }
+            .puzzle(((3, 3), puzzle.tiles.clone()), None)
+            .unwrap()
+            .run()
+            .unwrap()
+        };
+        let optimal = run(u64::MAX).cost;
+        // Stopped between the first goal popped and the proof, the path is kept
+        let stopped = (1..).map(run).find(|solution| solution.solved).unwrap();
+        assert_eq!(Some(Stop::Iterations), stopped.stop);
+        assert!(stopped.cost > optimal);
+        assert!(stopped.cost <= stopped.bound.unwrap() * optimal);
+    }
+    #[test]
+    fn test_weighted_bound() {
+        for (file, goal) in SAMPLES.iter() {
+            let optimal = solve(file, "astar", Heuristique::Manhattan, goal, 1.0).cost;
+            for weight in [1.5, 2.0, 4.0] {
+                let solution = solve(file, "astar", Heuristique::Manhattan, goal, weight);
+                assert!(solution.cost >= optimal);
+                assert!(solution.cost <= weight * optimal, "{}", file);
+                assert_eq!(Some(weight), solution.bound);
+            }
+        }
+    }
+    #[test]
+    fn test_tiled_strategy() {
+        let arg = "3\n1 2 3\n8 0 4\n7 6 5\n".to_string();
+        let puzzle = NPuzzle::new(
+            parse_file(arg).unwrap(),
+            Heuristique::WalkingDistance(None),
+            Algorithm::AStar,
+            "sandwich".to_string(),
+            Goal::Snail,
+            1.0,
+            u64::MAX,
+            None,
+            false,
+            None,
+            1,
+        );
+        assert!(matches!(puzzle, Err(NPuzzleError::TiledStrategy(_))));
+    }
+
+    /*
+     * Puzzles of the repository small enough to be solved by every
+     * algorithm, with their goal
+     */
+    pub(crate) const SAMPLES: [(&str, &str); 5] = [
+        ("puzzlesample.txt", "snail"),
+        ("simplepuzzle.txt", "snail"),
+        ("solvable3.txt", "std"),
+        ("solvable3std.txt", "std"),
+        ("solvable2x4std.txt", "std"),
+    ];
+
+    pub(crate) fn solve(
+        file: &str,
+        algorithm: &str,
+        heuristique: Heuristique,
+        goal: &str,
+        weight: f64,
+    ) -> Solution {
+        let path = format!("{}/puzzles/{}", env!("CARGO_MANIFEST_DIR"), file);
+        NPuzzle::new(
+            parse_file(std::fs::read_to_string(path).unwrap()).unwrap(),
+            heuristique,
+            Algorithm::parse(algorithm.to_string()).unwrap(),
+            "std".to_string(),
+            Goal::parse(goal.to_string(), String::new()),
+            weight,
+            u64::MAX,
+            None,
+            false,
+            None,
+            1,
+        )
+        .unwrap()
+        .run()
+        .unwrap()
+    }
 }
@@ -0,0 +1,212 @@
+use super::node::*;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+use std::hash::{BuildHasherDefault, Hash, Hasher};
+use std::sync::Arc;
+
+/*
+ * A node seen as a board state: hashed with its Zobrist hash,
+ * compared on the grid so that collisions stay harmless
+ */
+#[derive(Clone)]
+struct State(Arc<Node>);
+
+impl Hash for State {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.0.hash);
+    }
+}
+
+impl PartialEq for State {
+    fn eq(&self, other: &State) -> bool {
+        self.0.grid == other.0.grid
+    }
+}
+
+impl Eq for State {}
+
+/*
+ * The Zobrist hash is already well mixed, use it as is
+ */
+#[derive(Default)]
+struct ZobristHasher(u64);
+
+impl Hasher for ZobristHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = self.0.rotate_left(8) ^ b as u64;
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = n;
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+type StateMap = HashMap<State, f64, BuildHasherDefault<ZobristHasher>>;
+
+/*
+ * Open list: a binary heap of nodes plus the best g of every open state.
+ * A cheaper duplicate replaces the open one, whose heap entry is then
+ * skipped when it comes out.
+ */
+#[derive(Default)]
+pub struct OpenList {
+    heap: BinaryHeap<Candidate>,
+    best: StateMap,
+}
+
+impl fmt::Debug for OpenList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "OpenList({} states)", self.best.len())
+    }
+}
+
+impl OpenList {
+    pub fn new() -> OpenList {
+        OpenList::default()
+    }
+
+    /*
+     * Add the node unless the same state is already open with a lower or
+     * equal g, return whether it was added
+     */
+    pub fn push(&mut self, node: Arc<Node>) -> bool {
+        let state = State(node.clone());
+        match self.best.get(&state) {
+            Some(&g) if g <= node.g => false,
+            _ => {
+                self.best.insert(state, node.g);
+                self.heap.push(Candidate::new(node.f, node));
+                true
+            }
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<Arc<Node>> {
+        while let Some(candidate) = self.heap.pop() {
+            let state = State(candidate.node);
+            if self.best.get(&state) == Some(&state.0.g) {
+                self.best.remove(&state);
+                return Some(state.0);
+            }
+        }
+        None
+    }
+
+    /*
+     * Take the node out of the list if it is the open entry of its state
+     */
+    pub fn remove(&mut self, node: &Arc<Node>) -> bool {
+        let state = State(node.clone());
+        if self.best.get(&state) == Some(&node.g) {
+            self.best.remove(&state);
+            true
+        } else {
+            false
+        }
+    }
+
+    /*
+     * Lower bound of the f of the open nodes: the heap top may be a
+     * replaced entry, whose f is above the one of its open state
+     */
+    pub fn min_f(&self) -> Option<f64> {
+        self.heap.peek().map(|candidate| candidate.priority)
+    }
+
+    pub fn len(&self) -> usize {
+        self.best.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.best.is_empty()
+    }
+}
+
+/*
+ * Closed set: the best g with which every state was expanded
+ */
+#[derive(Default)]
+pub struct ClosedSet {
+    best: StateMap,
+}
+
+impl fmt::Debug for ClosedSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ClosedSet({} states)", self.best.len())
+    }
+}
+
+impl ClosedSet {
+    pub fn new() -> ClosedSet {
+        ClosedSet::default()
+    }
+
+    pub fn insert(&mut self, node: Arc<Node>) {
+        let g = node.g;
+        self.best.insert(State(node), g);
+    }
+
+    /*
+     * Whether the state was already expanded with a lower or equal g
+     */
+    pub fn contains_better(&self, node: &Arc<Node>) -> bool {
+        self.best
+            .get(&State(node.clone()))
+            .is_some_and(|&g| g <= node.g)
+    }
+
+    pub fn len(&self) -> usize {
+        self.best.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.best.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::board::*;
+    use super::super::utils::*;
+    use super::*;
+
+    fn node(grid: Vec<Vec<i64>>, g: f64) -> Arc<Node> {
+        let grid = Board::new(&grid).unwrap();
+        Arc::new(Node {
+            hash: zobrist(&grid),
+            grid,
+            f: g + 1f64,
+            g,
+            h: 1f64,
+            parent: None,
+        })
+    }
+
+    #[test]
+    fn test_open_list_keeps_best_g() {
+        let grid = vec![vec![1, 2], vec![3, 0]];
+        let mut open = OpenList::new();
+        assert!(open.push(node(grid.clone(), 5f64)));
+        assert!(!open.push(node(grid.clone(), 6f64)));
+        assert!(open.push(node(grid.clone(), 3f64)));
+        assert_eq!(1, open.len());
+        assert_eq!(3f64, open.pop().unwrap().g);
+        assert!(open.pop().is_none());
+    }
+
+    #[test]
+    fn test_closed_set() {
+        let grid = vec![vec![1, 2], vec![3, 0]];
+        let mut closed = ClosedSet::new();
+        closed.insert(node(grid.clone(), 4f64));
+        assert!(closed.contains_better(&node(grid.clone(), 4f64)));
+        assert!(!closed.contains_better(&node(grid.clone(), 2f64)));
+        assert!(!closed.contains_better(&node(vec![vec![1, 2], vec![0, 3]], 9f64)));
+    }
+}
@@ -1,162 +1,1235 @@
 use npuzzle::algorithm::*;
+use npuzzle::batch::{self, Entry, Job, Status};
+use npuzzle::board::Board;
+use npuzzle::cli::{self, Command, Matches, Opt};
+use npuzzle::config::{threads, Config};
+use npuzzle::diagnosis::Diagnosis;
+use npuzzle::error::NPuzzleError;
+use npuzzle::file::*;
+use npuzzle::generate::*;
 use npuzzle::goal::Goal;
+use npuzzle::hardest;
 use npuzzle::heuristique::Heuristique;
-use npuzzle::utils::*;
+use npuzzle::json::{self, Format};
+use npuzzle::perfect::{self, PerfectTable};
+use npuzzle::replay;
+use npuzzle::solution::*;
+use npuzzle::strategy::Strategy;
 use npuzzle::NPuzzle;
 use std::env;
-use std::error::Error;
 use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
 use std::process;
-use std::time::SystemTime;
-
-fn input_manager() -> Result<NPuzzle, Box<dyn Error>> {
-    let mut goal: Goal = Goal::Snail;
-    let mut heuristique: Heuristique = Heuristique::Manhattan;
-    let mut algorithm: Algorithm = Algorithm::AStar;
-    let mut strategy: String = "std".to_string();
-    let mut input: String = "".to_string();
-    let mut max_iteration: u64 = 10_000_000;
-    let mut debug: bool = false;
-    let mut args: Vec<String> = env::args().skip(1).rev().collect();
-    let mut thread: usize = 1;
-
-    while let Some(arg) = args.pop() {
-        match &arg as &str {
-            "--debug" | "-d" => debug = true,
-            "--input" | "-i" => {
-                if let Some(a) = args.pop() {
-                    if let Ok(a) = a.parse::<usize>() {
-                        if a < 6 && a > 0 {
-                            input = creat_new_rand(a);
-                        } else {
-                            println!("Map size has to be between 1 and 5 included");
-                            process::exit(0);
-                        }
-                    } else {
-                        input = fs::read_to_string(a)?;
-                    }
-                } else {
-                    println!("No file input given");
-                    process::exit(1);
-                }
+use std::time::{Duration, Instant};
+
+const PROGRAM: &str = "npuzzle";
+const ABOUT: &str = "Sliding puzzle solver";
+
+const HEURISTIQUE: Opt = Opt {
+    long: "heuristique",
+    short: Some('H'),
+    value: Some("<NAME>"),
+    help: "hamming, manhattan, linearconflict, wd, pdb[:PARTITION] or perfect (default manhattan)",
+};
+const GOAL: Opt = Opt {
+    long: "goal",
+    short: Some('o'),
+    value: Some("<GOAL>"),
+    help: "snail, std or a goal file (default snail)",
+};
+const FORMAT: Opt = Opt {
+    long: "format",
+    short: None,
+    value: Some("<FORMAT>"),
+    help: "text or json, for the puzzle read and the result written (default text)",
+};
+const CONVENTION: Opt = Opt {
+    long: "convention",
+    short: None,
+    value: Some("<WHO>"),
+    help: "Whose direction the move letters give: blank or tile (default blank)",
+};
+const TIMEOUT: Opt = Opt {
+    long: "timeout",
+    short: None,
+    value: Some("<SECONDS>"),
+    help: "Give up after this many seconds",
+};
+const SEED: Opt = Opt {
+    long: "seed",
+    short: None,
+    value: Some("<N>"),
+    help: "Seed of the random generator, to reproduce a puzzle",
+};
+const MOVES: Opt = Opt {
+    long: "moves",
+    short: None,
+    value: Some("<N>"),
+    help: "Random walk of N moves from the goal",
+};
+const DISTANCE: Opt = Opt {
+    long: "distance",
+    short: None,
+    value: Some("<N>"),
+    help: "Exactly N moves away from the goal, optimally",
+};
+
+/*
+ * Options of the commands which can make up their puzzle
+ */
+const RANDOM: &[Opt] = &[SEED, MOVES, DISTANCE];
+
+/*
+ * Options of every command which runs the solver
+ */
+const SEARCH: &[Opt] = &[
+    HEURISTIQUE,
+    Opt {
+        long: "algorithm",
+        short: Some('a'),
+        value: Some("<NAME>"),
+        help: "astar, greedy, bstar, idastar, bidirectional or arastar (default astar)",
+    },
+    Opt {
+        long: "strategy",
+        short: Some('s'),
+        value: Some("<NAME>"),
+        help: "std, sandwich or cross, how h weighs the cells (default std)",
+    },
+    GOAL,
+    Opt {
+        long: "weight",
+        short: Some('w'),
+        value: Some("<W>"),
+        help: "Weight of h, at least 1: the path is at most W times too long (default 1)",
+    },
+    Opt {
+        long: "iteration",
+        short: Some('n'),
+        value: Some("<N>"),
+        help: "Give up after this many iterations (default 10000000)",
+    },
+    TIMEOUT,
+    Opt {
+        long: "thread",
+        short: Some('t'),
+        value: Some("<N>"),
+        help: "Nodes expanded together, and pool threads: a number or auto (default 1, one thread per core)",
+    },
+    Opt {
+        long: "fix",
+        short: None,
+        value: None,
+        help: "Swap two tiles of an unsolvable puzzle to make it solvable",
+    },
+];
+
+const SOLVE: Command = Command {
+    name: "solve",
+    aliases: &[],
+    args: &["<PUZZLE>"],
+    about: "Solve a puzzle: a file, - for stdin, or a size (\"3\", \"2x4\") for a random one",
+    groups: &[
+        ("Search", SEARCH),
+        (
+            "Output",
+            &[
+                FORMAT,
+                Opt {
+                    long: "output",
+                    short: None,
+                    value: Some("<WHAT>"),
+                    help: "moves, grids or both (default grids)",
+                },
+                CONVENTION,
+                Opt {
+                    long: "debug",
+                    short: Some('d'),
+                    value: None,
+                    help: "Trace every expanded node",
+                },
+            ],
+        ),
+        ("Random puzzle", RANDOM),
+    ],
+};
+
+const CHECK: Command = Command {
+    name: "check",
+    aliases: &[],
+    args: &["<PUZZLE>"],
+    about: "Parse a puzzle and tell whether it can reach its goal",
+    groups: &[("Options", &[GOAL, FORMAT])],
+};
+
+const GENERATE: Command = Command {
+    name: "generate",
+    aliases: &[],
+    args: &["<SIZE>"],
+    about: "Print a random puzzle of a size (\"3\", \"2x4\")",
+    groups: &[(
+        "Options",
+        &[
+            GOAL,
+            SEED,
+            Opt {
+                long: "solvable",
+                short: None,
+                value: None,
+                help: "Only a solvable puzzle (the default with --distance)",
+            },
+            Opt {
+                long: "unsolvable",
+                short: None,
+                value: None,
+                help: "Only an unsolvable puzzle",
+            },
+            MOVES,
+            DISTANCE,
+            HEURISTIQUE,
+        ],
+    )],
+};
+
+const GOALS: Opt = Opt {
+    long: "goals",
+    short: None,
+    value: Some("<DIR>"),
+    help: "Directory holding the goal of each puzzle, under the same file name",
+};
+
+const SOLVE_BATCH: Command = Command {
+    name: "solve-batch",
+    aliases: &[],
+    args: &["<PATH>"],
+    about: "Solve every puzzle of a directory or multi-puzzle file in parallel, one line each",
+    groups: &[("Search", SEARCH), ("Goals", &[GOALS])],
+};
+
+const BENCH: Command = Command {
+    name: "bench",
+    aliases: &[],
+    args: &["<PATH>"],
+    about:
+        "Time the searches on every puzzle of a directory or multi-puzzle file, and compare them",
+    groups: &[
+        ("Search", SEARCH),
+        (
+            "Comparison",
+            &[
+                Opt {
+                    long: "heuristiques",
+                    short: None,
+                    value: Some("<LIST>"),
+                    help: "Comma-separated heuristiques to compare, instead of --heuristique",
+                },
+                Opt {
+                    long: "algorithms",
+                    short: None,
+                    value: Some("<LIST>"),
+                    help: "Comma-separated algorithms to compare, instead of --algorithm",
+                },
+            ],
+        ),
+        ("Goals", &[GOALS]),
+    ],
+};
+
+const VERIFY: Command = Command {
+    name: "verify",
+    aliases: &[],
+    args: &["<PUZZLE>", "<MOVES>"],
+    about: "Check that a move string (\"RDLU...\") solves a puzzle",
+    groups: &[
+        (
+            "Options",
+            &[
+                CONVENTION,
+                FORMAT,
+                Opt {
+                    long: "optimal",
+                    short: None,
+                    value: None,
+                    help: "Also solve the puzzle to tell whether the moves are optimal",
+                },
+            ],
+        ),
+        ("Search (with --optimal)", SEARCH),
+    ],
+};
+
+const PLAY: Command = Command {
+    name: "play",
+    aliases: &[],
+    args: &["<PUZZLE>"],
+    about: "Slide the tiles by hand, with hints from the solver, on a file or a random puzzle",
+    groups: &[
+        ("Options", &[CONVENTION]),
+        ("Hints", SEARCH),
+        ("Random puzzle", RANDOM),
+    ],
+};
+
+const HARDEST: Command = Command {
+    name: "hardest",
+    aliases: &[],
+    args: &["<SIZE>"],
+    about: "Find the configurations farthest from the goal: proven up to 9 cells, climbed beyond",
+    groups: &[(
+        "Options",
+        &[
+            GOAL,
+            Opt {
+                long: "top",
+                short: None,
+                value: Some("<K>"),
+                help: "Number of configurations to print (default 5)",
+            },
+            Opt {
+                long: "rounds",
+                short: None,
+                value: Some("<N>"),
+                help: "Number of climbs, beyond 9 cells (default 20)",
+            },
+            TIMEOUT,
+            SEED,
+            HEURISTIQUE,
+        ],
+    )],
+};
+
+const ENUMERATE: Command = Command {
+    name: "enumerate",
+    aliases: &[],
+    args: &["<SIZE>"],
+    about: "Histogram of the optimal distances of every configuration, up to 9 cells",
+    groups: &[(
+        "Options",
+        &[
+            GOAL,
+            Opt {
+                long: "save",
+                short: None,
+                value: Some("<FILE>"),
+                help: "Also write the table of distances to this file",
+            },
+            FORMAT,
+        ],
+    )],
+};
+
+const COMMANDS: &[&Command] = &[
+    &SOLVE,
+    &CHECK,
+    &GENERATE,
+    &SOLVE_BATCH,
+    &BENCH,
+    &VERIFY,
+    &PLAY,
+    &HARDEST,
+    &ENUMERATE,
+];
+
+/*
+ * The search options, validated. The rayon pool, on which the nodes of an
+ * epoch are expanded (and batches solved, pattern databases built), keeps
+ * one thread per core unless --thread sizes it to the nodes popped.
+ */
+fn config(m: &Matches) -> Result<Config, NPuzzleError> {
+    let default = Config::default();
+    let config = Config {
+        heuristique: heuristique(m)?,
+        algorithm: m
+            .parse("algorithm", Algorithm::parse)?
+            .unwrap_or(default.algorithm),
+        strategy: m
+            .parse("strategy", |s| {
+                Strategy::parse(s.clone(), Heuristique::Manhattan).map(|_| s)
+            })?
+            .unwrap_or(default.strategy),
+        goal: goal(m)?,
+        weight: m
+            .number(
+                "weight",
+                |&w: &f64| w >= 1.0,
+                "a number greater or equal to 1",
+            )?
+            .unwrap_or(default.weight),
+        max_iteration: m
+            .number("iteration", |_: &u64| true, "a positive integer")?
+            .unwrap_or(default.max_iteration),
+        timeout: timeout(m)?,
+        fix: m.flag("fix"),
+        trace: None,
+        on_improvement: None,
+        thread: m.parse("thread", threads)?.unwrap_or(default.thread),
+    };
+    if m.flag("thread") {
+        // Only fails when the pool was already built
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(config.thread)
+            .build_global()
+            .ok();
+    }
+    Ok(config)
+}
+
+fn heuristique(m: &Matches) -> Result<Heuristique, NPuzzleError> {
+    Ok(m.parse("heuristique", Heuristique::parse)?
+        .unwrap_or(Heuristique::Manhattan))
+}
+
+fn goal(m: &Matches) -> Result<Goal, NPuzzleError> {
+    Ok(m.parse("goal", |s| Ok(Goal::parse(s.clone(), s)))?
+        .unwrap_or(Goal::Snail))
+}
+
+fn timeout(m: &Matches) -> Result<Option<Duration>, NPuzzleError> {
+    Ok(m.number(
+        "timeout",
+        |&t: &f64| t > 0.0,
+        "a positive number of seconds",
+    )?
+    .map(Duration::from_secs_f64))
+}
+
+fn format(m: &Matches) -> Result<Format, NPuzzleError> {
+    Ok(m.parse("format", Format::parse)?.unwrap_or(Format::Text))
+}
+
+fn convention(m: &Matches) -> Result<Convention, NPuzzleError> {
+    Ok(m.parse("convention", Convention::parse)?
+        .unwrap_or(Convention::Blank))
+}
+
+fn seed(m: &Matches) -> Result<Option<u64>, NPuzzleError> {
+    m.number("seed", |_: &u64| true, "a positive integer")
+}
+
+fn scramble(m: &Matches) -> Result<Scramble, NPuzzleError> {
+    let moves = m.number("moves", |_: &usize| true, "a positive integer")?;
+    let distance = m.number("distance", |_: &usize| true, "a positive integer")?;
+    match (moves, distance) {
+        (Some(_), Some(_)) => Err(NPuzzleError::Usage(
+            "--moves and --distance cannot be used together".to_string(),
+        )),
+        (Some(n), None) => Ok(Scramble::Moves(n)),
+        (None, Some(n)) => Ok(Scramble::Distance(n)),
+        (None, None) => Ok(Scramble::Shuffle),
+    }
+}
+
+fn solvable(m: &Matches) -> Result<Option<bool>, NPuzzleError> {
+    match (m.flag("solvable"), m.flag("unsolvable")) {
+        (true, true) => Err(NPuzzleError::Usage(
+            "--solvable and --unsolvable cannot be used together".to_string(),
+        )),
+        (true, false) => Ok(Some(true)),
+        (false, true) => Ok(Some(false)),
+        (false, false) => Ok(None),
+    }
+}
+
+fn size_arg(arg: &str) -> Result<(i64, i64), NPuzzleError> {
+    size(arg).ok_or_else(|| NPuzzleError::Usage(format!("{} is not a size, \"3\" or \"2x4\"", arg)))
+}
+
+/*
+ * A puzzle read from the command line, with the text it was read from
+ */
+struct Loaded {
+    name: String,
+    source: String,
+    map: ParsedMap,
+    /* Given along with a JSON puzzle */
+    goal: Option<Goal>,
+}
+
+impl Loaded {
+    /*
+     * Value of a step which needs the puzzle, or its error reported
+     * against the puzzle text
+     */
+    fn or_fail<T>(&self, res: Result<T, NPuzzleError>, format: Format) -> T {
+        res.unwrap_or_else(|err| fail(err, format, &self.name, &self.source))
+    }
+}
+
+/*
+ * The puzzle named by arg: a file, - for stdin, or a size for a random
+ * solvable one (always in the text format)
+ */
+fn load(arg: &str, m: &Matches, goal: &Goal, format: Format) -> Result<Loaded, NPuzzleError> {
+    let (name, source, random) = match size(arg) {
+        Some(size) if !Path::new(arg).is_file() => {
+            let (scramble, seed) = (scramble(m)?, seed(m)?);
+            let generated = Generator::new(size, goal, heuristique(m)?, seed)
+                .and_then(|mut generator| generator.generate(scramble, Some(true)))
+                .unwrap_or_else(|err| fail(err, format, "", ""));
+            ("<random>".to_string(), generated.to_text(), true)
+        }
+        _ if arg == "-" => {
+            let mut stdin = String::new();
+            if let Err(e) = io::stdin().read_to_string(&mut stdin) {
+                fail(NPuzzleError::Read("<stdin>".to_string(), e), format, "", "");
             }
-            "--heuristique" | "-h" => {
-                if let Some(a) = args.pop() {
-                    heuristique = Heuristique::parse(a);
-                } else {
-                    println!("No heuristique given");
-                    process::exit(1);
-                }
+            ("<stdin>".to_string(), stdin, false)
+        }
+        _ => {
+            let text = fs::read_to_string(arg)
+                .unwrap_or_else(|e| fail(NPuzzleError::Read(arg.to_string(), e), format, arg, ""));
+            (arg.to_string(), text, false)
+        }
+    };
+    let (map, goal) = match format {
+        Format::Json if !random => json::parse_json(&source),
+        _ => parse_file(source.clone())
+            .map(|map| (map, None))
+            .map_err(NPuzzleError::from),
+    }
+    .unwrap_or_else(|err| fail(err, format, &name, &source));
+    Ok(Loaded {
+        name,
+        source,
+        map,
+        goal,
+    })
+}
+
+fn solve(m: &Matches) -> Result<(), NPuzzleError> {
+    let start = Instant::now();
+    let format = format(m)?;
+    let output = m.parse("output", Output::parse)?.unwrap_or(Output::Grids);
+    let convention = convention(m)?;
+    let mut config = config(m)?;
+    // Nothing but the result on stdout in JSON
+    config.trace = match (m.flag("debug"), format) {
+        (false, _) => None,
+        (true, Format::Text) => Some(|line| println!("{}", line)),
+        (true, Format::Json) => Some(|line| eprintln!("{}", line)),
+    };
+    if format == Format::Text {
+        config.on_improvement = Some(print_improvement);
+    }
+    let loaded = load(&m.args[0], m, &config.goal, format)?;
+    let puzzle = config.puzzle(loaded.map.clone(), loaded.goal.clone());
+    let mut puzzle = loaded.or_fail(puzzle, format);
+    let solution = match format {
+        Format::Json => {
+            let solution = loaded.or_fail(puzzle.run(), format);
+            println!("{}", json::report(&puzzle, &solution, convention));
+            solution
+        }
+        Format::Text => {
+            print_header(&puzzle);
+            println!("RUN !");
+            let solution = loaded.or_fail(puzzle.run(), format);
+            report(&solution, output, convention);
+            let dur = start.elapsed().as_millis();
+            println!("Time Complexity : {}.{} seconds", dur / 1000, dur % 1000);
+            solution
+        }
+    };
+    if !solution.solved {
+        process::exit(2);
+    }
+    Ok(())
+}
+
+/*
+ * Exits with 0 when the puzzle is solvable, 2 when it is not
+ */
+fn check(m: &Matches) -> Result<(), NPuzzleError> {
+    let format = format(m)?;
+    let goal = goal(m)?;
+    let loaded = load(&m.args[0], m, &goal, format)?;
+    let (size, initial) = &loaded.map;
+    let goal = loaded.goal.clone().unwrap_or(goal);
+    let target = loaded.or_fail(goal.generate(*size, initial), format);
+    let board = |grid| loaded.or_fail(Board::new(grid).ok_or(NPuzzleError::BoardTooLarge), format);
+    let (first, last) = (board(initial), board(&target));
+    let diagnosis = Diagnosis::new(initial, &target);
+    let solvable = diagnosis.solvability.solvable;
+    match format {
+        Format::Json => println!("{}", json::check(*size, initial, &target, &diagnosis)),
+        Format::Text => {
+            println!("SIZE : {:?}", size);
+            println!("INITIAL : {:?}", first);
+            println!("GOAL : {:?}", last);
+            println!("SOLVABILITY : {}", diagnosis.solvability);
+            if solvable {
+                println!("SOLVABLE");
+            } else {
+                println!("UNSOLVABLE");
+                println!("{}", diagnosis);
             }
-            "--algorithm" | "--algo" | "-a" => {
-                if let Some(a) = args.pop() {
-                    algorithm = Algorithm::parse(a);
-                } else {
-                    println!("No algorithm given");
-                    process::exit(1);
-                }
+        }
+    }
+    process::exit(if solvable { 0 } else { 2 });
+}
+
+fn generate(m: &Matches) -> Result<(), NPuzzleError> {
+    let size = size_arg(&m.args[0])?;
+    let (scramble, solvable) = (scramble(m)?, solvable(m)?);
+    let generated = Generator::new(size, &goal(m)?, heuristique(m)?, seed(m)?)
+        .and_then(|mut generator| generator.generate(scramble, solvable))
+        .unwrap_or_else(|err| fail(err, Format::Text, "", ""));
+    print!("{}", generated.to_text());
+    Ok(())
+}
+
+/*
+ * One status line per puzzle, then the totals; exits with 2 when a puzzle
+ * timed out or was given up
+ */
+fn solve_batch(m: &Matches) -> Result<(), NPuzzleError> {
+    let config = config(m)?;
+    let start = Instant::now();
+    let entries = batch::solve(batch_jobs(m, &config), |map, goal| {
+        config.puzzle(map, Some(goal))
+    });
+    print_batch(&entries, start.elapsed());
+    exit_batch(&entries);
+    Ok(())
+}
+
+/*
+ * The puzzles of the path argument, each with its goal
+ */
+fn batch_jobs(m: &Matches, config: &Config) -> Vec<Job> {
+    let path = &m.args[0];
+    batch::jobs(
+        Path::new(path),
+        m.value("goals").map(Path::new),
+        &config.goal,
+    )
+    .unwrap_or_else(|err| fail(err, Format::Text, path, ""))
+}
+
+/*
+ * Exits with 2 when one of the entries timed out or was given up
+ */
+fn exit_batch<'a>(entries: impl IntoIterator<Item = &'a Entry>) {
+    if entries
+        .into_iter()
+        .any(|e| matches!(e.status, Status::Timeout | Status::GaveUp))
+    {
+        process::exit(2);
+    }
+}
+
+/*
+ * Exits with 2 when a puzzle timed out or was given up
+ */
+fn bench(m: &Matches) -> Result<(), NPuzzleError> {
+    let config = config(m)?;
+    let algorithms = m
+        .parse("algorithms", |s| listed(&s, Algorithm::parse))?
+        .unwrap_or_else(|| {
+            let name = m.value("algorithm").unwrap_or("astar");
+            vec![(name.to_string(), config.algorithm)]
+        });
+    let heuristiques = m
+        .parse("heuristiques", |s| listed(&s, Heuristique::parse))?
+        .unwrap_or_else(|| {
+            let name = m.value("heuristique").unwrap_or("manhattan");
+            vec![(name.to_string(), config.heuristique.clone())]
+        });
+    let mut runs = vec![];
+    // One configuration at a time, so that they do not slow each other down
+    for (algorithm, heuristique) in algorithms
+        .iter()
+        .flat_map(|a| heuristiques.iter().map(move |h| (a, h)))
+    {
+        let config = Config {
+            algorithm: algorithm.1,
+            heuristique: heuristique.1.clone(),
+            ..config.clone()
+        };
+        let jobs = batch_jobs(m, &config);
+        let start = Instant::now();
+        let entries = batch::solve(jobs, |map, goal| config.puzzle(map, Some(goal)));
+        let name = format!("{} / {}", algorithm.0, heuristique.0);
+        runs.push((name, entries, start.elapsed()));
+    }
+    match &runs[..] {
+        [(_, entries, elapsed)] => print_batch(entries, *elapsed),
+        _ => print_comparison(&runs),
+    }
+    exit_batch(runs.iter().flat_map(|(_, entries, _)| entries));
+    Ok(())
+}
+
+/*
+ * Every name of a comma-separated list, with what it parses to
+ */
+fn listed<T>(
+    list: &str,
+    parse: impl Fn(String) -> Result<T, NPuzzleError>,
+) -> Result<Vec<(String, T)>, NPuzzleError> {
+    list.split(',')
+        .map(|name| parse(name.to_string()).map(|value| (name.to_string(), value)))
+        .collect()
+}
+
+/*
+ * Exits with 0 when the moves solve the puzzle (optimally, if asked and
+ * known), 2 otherwise
+ */
+fn verify(m: &Matches) -> Result<(), NPuzzleError> {
+    let format = format(m)?;
+    let convention = convention(m)?;
+    let config = config(m)?;
+    let moves = replay::parse_moves(&m.args[1], convention)?;
+    let loaded = load(&m.args[0], m, &config.goal, format)?;
+    let (size, tiles) = &loaded.map;
+    let goal = loaded.goal.clone().unwrap_or_else(|| config.goal.clone());
+    let target = loaded.or_fail(goal.generate(*size, tiles), format);
+    let board = |grid| loaded.or_fail(Board::new(grid).ok_or(NPuzzleError::BoardTooLarge), format);
+    let replay = replay::replay(&board(tiles), &board(&target), &moves);
+    let optimal = match m.flag("optimal") {
+        true => {
+            let puzzle = config.puzzle(loaded.map.clone(), Some(Goal::Tiles(target)));
+            let mut puzzle = loaded.or_fail(puzzle, format);
+            Some(loaded.or_fail(puzzle.run(), format))
+        }
+        false => None,
+    };
+    let shortest = optimal
+        .as_ref()
+        .filter(|solution| solution.solved)
+        .map(|solution| solution.cost);
+    let valid = replay.solved && shortest.is_none_or(|cost| moves.len() as f64 <= cost);
+    if format == Format::Json {
+        println!("{}", json::verify(&replay, shortest));
+        process::exit(if valid { 0 } else { 2 });
+    }
+    let letter = |direction: Direction| match convention {
+        Convention::Blank => direction,
+        Convention::Tile => direction.opposite(),
+    };
+    match replay.illegal {
+        Some((n, direction)) => println!(
+            "INVALID : move {} ({}) leaves the board",
+            n + 1,
+            letter(direction)
+        ),
+        None if !replay.solved => {
+            println!(
+                "INVALID : the {} moves do not reach the goal, they end on",
+                moves.len()
+            );
+            print_grid(&replay.path.last().unwrap().grid());
+        }
+        None => println!("VALID : {} moves reach the goal", moves.len()),
+    }
+    match (optimal, shortest) {
+        (_, Some(cost)) if moves.len() as f64 <= cost => {
+            println!("OPTIMAL : no solution is shorter")
+        }
+        (_, Some(cost)) => println!("NOT OPTIMAL : {} moves are enough", cost),
+        (Some(solution), None) => println!(
+            "OPTIMALITY UNKNOWN : {}",
+            solution
+                .stop
+                .map_or("no path found".to_string(), |s| s.to_string())
+        ),
+        (None, None) => {}
+    }
+    process::exit(if valid { 0 } else { 2 });
+}
+
+const PLAY_HELP: &str = "Moves: letters (\"RDLU\") or the number of the tile to slide; \
+hint, undo, help or quit";
+
+fn play(m: &Matches) -> Result<(), NPuzzleError> {
+    let convention = convention(m)?;
+    let mut config = config(m)?;
+    let loaded = load(&m.args[0], m, &config.goal, Format::Text)?;
+    let puzzle = loaded.or_fail(
+        config.puzzle(loaded.map.clone(), loaded.goal.clone()),
+        Format::Text,
+    );
+    // Hints reuse the tables built for the goal
+    loaded.or_fail(config.heuristique.init(&puzzle.goal), Format::Text);
+    let letter = |direction: Direction| match convention {
+        Convention::Blank => direction,
+        Convention::Tile => direction.opposite(),
+    };
+    let mut path = vec![puzzle.initial.clone()];
+    let mut moves: Vec<Direction> = vec![];
+    println!("{}", PLAY_HELP);
+    print_grid(&puzzle.initial.grid());
+    let mut lines = io::stdin().lock().lines();
+    while *path.last().unwrap() != puzzle.goal {
+        print!("> ");
+        io::stdout().flush().ok();
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => {
+                println!();
+                return Ok(());
             }
-            "--strategy" | "--strat" | "-s" => {
-                if let Some(a) = args.pop() {
-                    strategy = a;
-                } else {
-                    println!("No strategy given");
-                    process::exit(1);
-                }
+        };
+        let current = path.last().unwrap().clone();
+        match &line.trim().to_lowercase() as &str {
+            "" => continue,
+            "q" | "quit" => return Ok(()),
+            "?" | "help" => println!("{}", PLAY_HELP),
+            "u" | "undo" if path.len() > 1 => {
+                path.pop();
+                moves.pop();
             }
-            "--goal" | "-o" => {
-                if let Some(a) = args.pop() {
-                    goal = match &a.to_lowercase() as &str {
-                        "custom" | "cstm" => {
-                            if let Some(b) = args.pop() {
-                                Goal::parse(a, b)
-                            } else {
-                                println!("Goal File Missing");
-                                process::exit(1);
-                            }
-                        }
-                        "std" | "standard" | "snail" => Goal::parse(a, "".to_string()),
-                        _ => {
-                            println!("Goal arguments missing or invalid");
-                            process::exit(1);
-                        }
+            "u" | "undo" => println!("Nothing to undo"),
+            "h" | "hint" => hint(&config, &current, &puzzle.goal, convention),
+            line => match line.parse::<i64>() {
+                Ok(tile) => match replay::slide_tile(&current, tile) {
+                    Some((next, direction)) => {
+                        path.push(next);
+                        moves.push(direction);
                     }
-                } else {
-                    println!("No goal given");
-                    process::exit(1);
-                }
-            }
-            "--iteration" | "-n" => {
-                max_iteration = match args.pop() {
-                    Some(v) => {
-                        if let Ok(n) = v.parse::<u64>() {
-                            n
-                        } else {
-                            println!("Cannot parse the number of iterations");
-                            process::exit(1);
+                    None => println!("Tile {} is not next to the empty space", tile),
+                },
+                Err(_) => match replay::parse_moves(line, convention) {
+                    Ok(directions) => {
+                        for direction in directions {
+                            match replay::step(path.last().unwrap(), direction) {
+                                Some(next) => {
+                                    path.push(next);
+                                    moves.push(direction);
+                                }
+                                None => {
+                                    println!("Cannot move {} from here", letter(direction));
+                                    break;
+                                }
+                            }
                         }
                     }
-                    _ => {
-                        println!("number of iteration missing");
-                        process::exit(1);
-                    }
-                };
+                    Err(err) => println!("{}", err),
+                },
+            },
+        }
+        print_grid(&path.last().unwrap().grid());
+    }
+    let notation: String = moves.iter().map(|&d| letter(d).to_string()).collect();
+    println!("SOLVED in {} moves : {}", moves.len(), notation);
+    Ok(())
+}
+
+/*
+ * First move of the path the solver finds from board
+ */
+fn hint(config: &Config, board: &Board, goal: &Board, convention: Convention) {
+    let size = (board.rows() as i64, board.cols() as i64);
+    let mut puzzle = match config.puzzle((size, board.grid()), Some(Goal::Tiles(goal.grid()))) {
+        Ok(puzzle) => puzzle,
+        Err(err) => return println!("{}", err),
+    };
+    let solution = match puzzle.run() {
+        Ok(solution) => solution,
+        Err(err) => return println!("{}", err),
+    };
+    match solution.moves.first() {
+        Some(step) if solution.solved => println!(
+            "HINT : {} (tile {}), {} moves left",
+            step.letter(convention),
+            step.tile,
+            solution.cost
+        ),
+        _ => println!(
+            "No hint : {}",
+            solution
+                .stop
+                .map_or("no path found".to_string(), |s| s.to_string())
+        ),
+    }
+}
+
+/*
+ * Exits with 0 once the configurations are printed
+ */
+fn hardest(m: &Matches) -> Result<(), NPuzzleError> {
+    let size = size_arg(&m.args[0])?;
+    let top = m
+        .number("top", |&n: &usize| n > 0, "a strictly positive integer")?
+        .unwrap_or(5);
+    let rounds = m
+        .number("rounds", |&n: &usize| n > 0, "a strictly positive integer")?
+        .unwrap_or(20);
+    let timeout = timeout(m)?;
+    let mut generator = Generator::new(size, &goal(m)?, heuristique(m)?, seed(m)?)
+        .unwrap_or_else(|err| fail(err, Format::Text, "", ""));
+    let (found, how) = if (size.0 * size.1) as usize <= perfect::MAX_CELLS {
+        let table = PerfectTable::load_or_build(generator.goal())
+            .unwrap_or_else(|err| fail(err, Format::Text, "", ""));
+        (hardest::retrograde(&table, top), "proven by retrograde BFS")
+    } else {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        (
+            hardest::hill_climb(&mut generator, top, rounds, deadline)?,
+            "local maximum, optimal distance by IDA*",
+        )
+    };
+    print!("{}", hardest::to_text(&found, how));
+    Ok(())
+}
+
+fn enumerate(m: &Matches) -> Result<(), NPuzzleError> {
+    let size = size_arg(&m.args[0])?;
+    let format = format(m)?;
+    let table = Generator::new(size, &goal(m)?, Heuristique::Manhattan, None)
+        .and_then(|generator| PerfectTable::load_or_build(generator.goal()))
+        .unwrap_or_else(|err| fail(err, format, "", ""));
+    if let Some(path) = m.value("save") {
+        if let Err(e) = table.save(Path::new(path)) {
+            fail(NPuzzleError::Write(path.to_string(), e), format, "", "");
+        }
+    }
+    print_histogram(size, &table, format);
+    Ok(())
+}
+
+/*
+ * Report of a command line which cannot be understood, and exit
+ */
+fn usage(command: Option<&Command>, err: NPuzzleError) -> ! {
+    eprintln!("error: {}", err);
+    match command {
+        Some(command) => eprintln!("Run '{} {} --help' for its options.", PROGRAM, command.name),
+        None => eprintln!("Run '{} --help' for the commands.", PROGRAM),
+    }
+    process::exit(1);
+}
+
+/*
+ * Report of an error which prevents the search, and exit: on stderr, but
+ * for JSON which is always written to stdout
+ */
+fn fail(err: NPuzzleError, format: Format, name: &str, source: &str) -> ! {
+    match (&err, format) {
+        (_, Format::Json) => println!("{}", json::error(&err)),
+        (NPuzzleError::Parse(err), _) => print_parse_error(name, source, err),
+        (NPuzzleError::GoalParse(path, err), _) => {
+            print_parse_error(path, &fs::read_to_string(path).unwrap_or_default(), err)
+        }
+        (NPuzzleError::Unsolvable(diagnosis), _) => {
+            eprintln!("SOLVABILITY : {}", diagnosis.solvability);
+            eprintln!("{}", err);
+            eprintln!("{}", diagnosis);
+        }
+        _ => eprintln!("{}", err),
+    }
+    process::exit(1);
+}
+
+/*
+ * "3" for a square board, "2x4" for 2 rows and 4 columns
+ */
+fn size(s: &str) -> Option<(i64, i64)> {
+    let mut dims = s
+        .split('x')
+        .map(|n| n.parse::<i64>().ok().filter(|&n| n > 0));
+    match (dims.next(), dims.next(), dims.next()) {
+        (Some(Some(n)), None, None) => Some((n, n)),
+        (Some(Some(rows)), Some(Some(cols)), None) => Some((rows, cols)),
+        _ => None,
+    }
+}
+
+fn print_improvement(n: usize, s: &Improvement) {
+    println!(
+        "SOLUTION {} (weight {}) : {} moves, cost <= {:.3} x optimal",
+        n, s.weight, s.moves, s.bound
+    );
+}
+
+fn print_header(puzzle: &NPuzzle) {
+    println!("SIZE : {:?}", puzzle.size);
+    println!("INITIAL : {:?}", puzzle.initial);
+    println!("GOAL : {:?}", puzzle.goal);
+    println!("SOLVABILITY : {}", puzzle.solvability);
+    if let Some((_, a, b)) = puzzle.fixed {
+        println!("FIXED by swapping {} and {} : {:?}", a, b, puzzle.initial);
+    }
+    println!("Strategy: {:?}", puzzle.strategy);
+}
+
+/*
+ * Display of the solved puzzle, or of the partial path to the closest state
+ */
+fn report(solution: &Solution, output: Output, convention: Convention) {
+    let stats = &solution.stats;
+    match (solution.solved, solution.stop) {
+        (true, stop) => {
+            if let Some(stop) = stop {
+                println!("STOPPED : {}", stop);
             }
-            "--thread" | "--thrd" | "--th" | "-t" => {
-                if let Some(a) = args.pop() {
-                    if let Ok(a) = a.parse::<usize>() {
-                        if a > 0 && a < 5 {
-                            thread = a
-                        } else {
-                            println!("Put between 1 and 4 threads");
-                            process::exit(1);
-                        }
-                    } else {
-                        println!("Wrong number of thread");
-                        process::exit(1);
-                    }
-                } else {
-                    println!("Give the number of thread you want");
-                    process::exit(1);
-                }
+            println!("RESOLVED :");
+            print_path(solution, output, convention);
+            println!("Number of moves: {}", solution.cost);
+            if let Some(bound) = solution.bound {
+                println!("Suboptimality bound : cost <= {} x optimal", bound);
             }
-            _ => {
-                println!("Argument not recognized");
-                process::exit(1);
+        }
+        (false, stop) => {
+            if let Some(stop) = stop {
+                println!("GAVE UP : {}", stop);
             }
+            println!("Closest state found (h = {}) :", solution.h);
+            print_path(solution, output, convention);
+            println!("Number of moves (partial path): {}", solution.cost);
+        }
+    }
+    if !stats.thresholds.is_empty() {
+        println!("Number of passes : {}", stats.thresholds.len());
+        println!("Thresholds of the passes : {:?}", stats.thresholds);
+    }
+    println!("Number of iterations : {}", stats.iterations);
+    println!("Complexity Size (Max States): {}", stats.max_states);
+}
+
+/*
+ * Every grid of the path and/or the move string with the tiles moved
+ */
+fn print_path(solution: &Solution, output: Output, convention: Convention) {
+    if output != Output::Moves {
+        for grid in solution.path.iter() {
+            print_grid(grid);
+            println!();
+        }
+    }
+    if output != Output::Grids {
+        let tiles: Vec<String> = solution.tiles().iter().map(|t| t.to_string()).collect();
+        let name = match convention {
+            Convention::Blank => "blank",
+            Convention::Tile => "tile",
         };
+        println!("MOVES ({}) : {}", name, solution.notation(convention));
+        println!("TILES : {}", tiles.join(" "));
     }
-    let puzzle = NPuzzle::new(
-        input,
-        heuristique,
-        algorithm,
-        strategy,
-        goal,
-        max_iteration,
-        debug,
-        2usize,
-    )
-    .unwrap_or_else(|err| {
-        eprintln!("Problem with the format of the map : {}", err);
-        process::exit(1);
-    });
-    Ok(puzzle)
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let now = SystemTime::now();
-    match input_manager() {
-        Ok(mut puzzle) => puzzle.run(),
-        Err(e) => println!("Sorry, You have a mental disease : {}", e),
+fn print_grid(grid: &[Vec<i64>]) {
+    for row in grid.iter() {
+        let row: Vec<String> = row.iter().map(|t| t.to_string()).collect();
+        println!("{}", row.join(" "));
     }
-    match now.elapsed() {
-        Ok(elapsed) => {
-            let dur = elapsed.as_millis();
-            println!("Time Complexity : {}.{} seconds", dur / 1000, dur % 1000);
+}
+
+/*
+ * One line per puzzle of a batch, then why the failed ones failed
+ */
+fn print_batch(entries: &[Entry], elapsed: Duration) {
+    let width = entries
+        .iter()
+        .map(|e| e.name.chars().count())
+        .chain(Some(6))
+        .max()
+        .unwrap();
+    println!(
+        "{:<width$}  {:<11}  {:>6}  {:>10}  {:>10}  {:>9}",
+        "PUZZLE",
+        "STATUS",
+        "MOVES",
+        "ITERATIONS",
+        "STATES",
+        "TIME",
+        width = width
+    );
+    for entry in entries.iter() {
+        let status = entry.status.to_string();
+        match &entry.solution {
+            Some(s) => println!(
+                "{:<width$}  {:<11}  {:>6}  {:>10}  {:>10}  {:>8.3}s",
+                entry.name,
+                status,
+                s.cost,
+                s.stats.iterations,
+                s.stats.max_states,
+                s.stats.elapsed.as_secs_f64(),
+                width = width
+            ),
+            None => println!(
+                "{:<width$}  {:<11}  {:>6}  {:>10}  {:>10}  {:>9}",
+                entry.name,
+                status,
+                "-",
+                "-",
+                "-",
+                "-",
+                width = width
+            ),
         }
-        Err(e) => {
-            println!("Error: {:?}", e);
+    }
+    let solved = entries
+        .iter()
+        .filter(|e| e.status == Status::Solved)
+        .count();
+    println!("Solved {} / {}", solved, entries.len());
+    let total: Duration = entries
+        .iter()
+        .filter_map(|e| e.solution.as_ref())
+        .map(|s| s.stats.elapsed)
+        .sum();
+    println!(
+        "Total time : {:.3}s ({:.3}s of searches)",
+        elapsed.as_secs_f64(),
+        total.as_secs_f64()
+    );
+    print_batch_errors(entries);
+}
+
+/*
+ * One line per configuration benchmarked, over the same puzzles: totals
+ * over the puzzles solved, the largest open list over all of them
+ */
+fn print_comparison(runs: &[(String, Vec<Entry>, Duration)]) {
+    let width = runs
+        .iter()
+        .map(|(name, _, _)| name.chars().count())
+        .chain(Some(6))
+        .max()
+        .unwrap();
+    println!(
+        "{:<width$}  {:>9}  {:>8}  {:>12}  {:>10}  {:>9}",
+        "SEARCH",
+        "SOLVED",
+        "MOVES",
+        "ITERATIONS",
+        "STATES",
+        "TIME",
+        width = width
+    );
+    for (name, entries, _) in runs.iter() {
+        let solved: Vec<&Solution> = entries
+            .iter()
+            .filter(|e| e.status == Status::Solved)
+            .filter_map(|e| e.solution.as_ref())
+            .collect();
+        let all = entries.iter().filter_map(|e| e.solution.as_ref());
+        println!(
+            "{:<width$}  {:>9}  {:>8}  {:>12}  {:>10}  {:>8.3}s",
+            name,
+            format!("{}/{}", solved.len(), entries.len()),
+            solved.iter().map(|s| s.cost).sum::<f64>(),
+            solved.iter().map(|s| s.stats.iterations).sum::<u64>(),
+            all.clone().map(|s| s.stats.max_states).max().unwrap_or(0),
+            all.map(|s| s.stats.elapsed).sum::<Duration>().as_secs_f64(),
+            width = width
+        );
+    }
+    if let Some((_, entries, _)) = runs.first() {
+        print_batch_errors(entries);
+    }
+}
+
+/*
+ * Why the puzzles which could not be searched failed
+ */
+fn print_batch_errors(entries: &[Entry]) {
+    for entry in entries.iter() {
+        if let Some(err) = &entry.error {
+            match err {
+                NPuzzleError::Parse(e) => {
+                    let span = e.span();
+                    let name = entry.name.split(':').next().unwrap();
+                    eprintln!("{}:{}:{} : {}", name, span.line, span.column, e);
+                }
+                _ => eprintln!("{} : {}", entry.name, err),
+            }
         }
     }
-    Ok(())
+}
+
+/*
+ * Number of configurations at each optimal distance from the goal
+ */
+fn print_histogram(size: (i64, i64), table: &PerfectTable, format: Format) {
+    let histogram = table.histogram();
+    let total: usize = histogram.iter().sum();
+    if format == Format::Json {
+        println!(
+            "{}",
+            serde_json::json!({
+                "size": size,
+                "goal": table.goal(),
+                "histogram": histogram,
+                "configurations": total,
+                "farthest": histogram.len() - 1,
+            })
+        );
+        return;
+    }
+    println!("# {}x{} table {}", size.0, size.1, table.origin());
+    println!("{:>8}  {:>14}", "DISTANCE", "CONFIGURATIONS");
+    for (distance, count) in histogram.iter().enumerate() {
+        println!("{:>8}  {:>14}", distance, count);
+    }
+    println!(
+        "{} configurations, the farthest {} moves away",
+        total,
+        histogram.len() - 1
+    );
+}
+
+/*
+ * Parse errors printed like a compiler, with a caret under the token
+ */
+fn print_parse_error(name: &str, source: &str, err: &FileParsingError) {
+    let span = err.span();
+    let line = source.lines().nth(span.line - 1).unwrap_or("");
+    let margin = " ".repeat(span.line.to_string().len());
+    // Keep the tabs before the token so that the caret lines up
+    let indent: String = line
+        .chars()
+        .take(span.column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    eprintln!("error: {}", err);
+    eprintln!("{}--> {}:{}:{}", margin, name, span.line, span.column);
+    eprintln!("{} |", margin);
+    eprintln!("{} | {}", span.line, line);
+    eprintln!(
+        "{} | {}{}",
+        margin,
+        indent,
+        "^".repeat(span.token.chars().count().max(1))
+    );
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let name = args.next().unwrap_or_default();
+    if name.is_empty() || name == "--help" || name == "-h" || name == "help" {
+        print!("{}", cli::help(PROGRAM, ABOUT, COMMANDS));
+        process::exit(if name.is_empty() { 1 } else { 0 });
+    }
+    let command = cli::find(COMMANDS, &name).unwrap_or_else(|| {
+        usage(
+            None,
+            NPuzzleError::Usage(format!("unknown command {}", name)),
+        )
+    });
+    let m = command
+        .parse(args.collect())
+        .unwrap_or_else(|err| usage(Some(command), err));
+    if m.help {
+        print!("{}", command.help(PROGRAM));
+        process::exit(0);
+    }
+    let res = match command.name {
+        "solve" => solve(&m),
+        "check" => check(&m),
+        "generate" => generate(&m),
+        "solve-batch" => solve_batch(&m),
+        "bench" => bench(&m),
+        "verify" => verify(&m),
+        "play" => play(&m),
+        "hardest" => hardest(&m),
+        _ => enumerate(&m),
+    };
+    if let Err(err) = res {
+        usage(Some(command), err);
+    }
 }
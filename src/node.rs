@@ -3,13 +3,16 @@ use std::fmt;
 use std::sync::Arc;
 
 use super::algorithm::*;
+use super::board::*;
 use super::strategy::*;
+use super::utils::*;
 
 type Link = Option<Arc<Node>>;
 
 #[derive(Clone)]
 pub struct Node {
-    pub grid: Vec<Vec<i64>>,
+    pub grid: Board,
+    pub hash: u64,
     pub f: f64,
     pub g: f64,
     pub h: f64,
@@ -18,28 +21,49 @@ pub struct Node {
 
 impl Node {
     pub fn new(
-        grid: Vec<Vec<i64>>,
+        grid: Board,
         parent: Link,
-        goal: &Vec<Vec<i64>>,
+        goal: &Board,
         algorithm: &Algorithm,
         strategy: &Strategy,
+        weight: f64,
+    ) -> Node {
+        let h = strategy.process(&grid, goal);
+        let hash = zobrist(&grid);
+        Self::with_h(grid, hash, parent, h, algorithm, weight)
+    }
+
+    /*
+     * Node whose hash and h are already known, e.g. computed from its parent's
+     */
+    pub fn with_h(
+        grid: Board,
+        hash: u64,
+        parent: Link,
+        h: f64,
+        algorithm: &Algorithm,
+        weight: f64,
     ) -> Node {
         let p = parent.clone();
         let g = match p {
             Some(n) => n.g + 1.0,
             None => 0.0,
         };
-        let h = strategy.process(&grid, goal);
         let p = parent.clone();
         let f = match algorithm {
-            Algorithm::AStar | Algorithm::Greedy => h + g,
+            Algorithm::AStar
+            | Algorithm::Greedy
+            | Algorithm::IdaStar
+            | Algorithm::Bidirectional
+            | Algorithm::AraStar => weight * h + g,
             Algorithm::BStar => match p {
-                Some(n) => (h + g) - (n.h + n.g),
+                Some(n) => (weight * h + g) - (weight * n.h + n.g),
                 None => 0.0,
             },
         };
         Node {
             grid,
+            hash,
             f,
             h,
             g,
@@ -48,29 +72,47 @@ impl Node {
     }
 }
 
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        self.grid == other.grid && self.f <= other.f && self.g <= other.g
+    }
+}
+
 /*
- * Warning: Order for Node is reverse to make the binary heap a min-heap
+ * Node wrapped with its priority, f or one computed outside of it
+ * Warning: the order is reversed, ties broken on g, to make the binary
+ * heap a min-heap
  */
-impl Eq for Node {}
+pub struct Candidate {
+    pub priority: f64,
+    pub node: Arc<Node>,
+}
 
-impl Ord for Node {
+impl Candidate {
+    pub fn new(priority: f64, node: Arc<Node>) -> Candidate {
+        Candidate { priority, node }
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Candidate) -> bool {
+        self.priority == other.priority && self.node.g == other.node.g
+    }
+}
+
+impl Ord for Candidate {
     fn cmp(&self, other: &Self) -> Ordering {
-        let ord = self.f.partial_cmp(&other.f).unwrap();
-        match ord {
+        match self.priority.partial_cmp(&other.priority).unwrap() {
             Ordering::Greater => Ordering::Less,
             Ordering::Less => Ordering::Greater,
-            Ordering::Equal => self.g.partial_cmp(&other.g).unwrap(),
+            Ordering::Equal => self.node.g.partial_cmp(&other.node.g).unwrap(),
         }
     }
 }
 
-impl PartialEq for Node {
-    fn eq(&self, other: &Node) -> bool {
-        self.grid == other.grid && self.f <= other.f && self.g <= other.g
-    }
-}
-
-impl PartialOrd for Node {
+impl PartialOrd for Candidate {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
@@ -83,18 +125,7 @@ impl fmt::Display for Node {
             "f: {} <- h: {} + g: {}
 {}
 ",
-            self.f,
-            self.h,
-            self.g,
-            self.grid
-                .iter()
-                .map(|x| x
-                    .iter()
-                    .map(|&y| y.to_string())
-                    .collect::<Vec<String>>()
-                    .join(" "))
-                .collect::<Vec<String>>()
-                .join("\n"),
+            self.f, self.h, self.g, self.grid,
         )
     }
 }
@@ -111,19 +142,21 @@ mod tests {
     #[test]
     fn test_node_equality() {
         let first = Node {
-            grid: vec![vec![0; 3]; 3],
+            grid: Board::new(&vec![vec![0; 3]; 3]).unwrap(),
+            hash: 0,
             f: 10f64,
             g: 5.0f64,
             h: 5.0f64,
             parent: None,
         };
         let second = first.clone();
-        assert_eq!(true, first == second);
+        assert!(first == second);
     }
     #[test]
     fn test_node_inequality() {
         let first = Node {
-            grid: vec![vec![0; 3]; 3],
+            grid: Board::new(&vec![vec![0; 3]; 3]).unwrap(),
+            hash: 0,
             f: 10f64,
             g: 5.0f64,
             h: 5.0f64,
@@ -131,7 +164,20 @@ mod tests {
         };
         let mut second = first.clone();
         second.g = 3.0f64;
-        assert_eq!(false, first == second);
+        assert!(first != second);
+    }
+    #[test]
+    fn test_node_grid_inequality() {
+        let first = Node {
+            grid: Board::new(&[vec![1, 2, 3], vec![8, 0, 4], vec![7, 6, 5]]).unwrap(),
+            hash: 0,
+            f: 10f64,
+            g: 5.0f64,
+            h: 5.0f64,
+            parent: None,
+        };
+        let mut second = first.clone();
+        second.grid = first.grid.slide((0, 1));
+        assert!(first != second);
     }
-
 }
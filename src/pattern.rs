@@ -0,0 +1,468 @@
+use super::board::*;
+use super::error::*;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/*
+ * Binary cache layout, bump VERSION whenever it changes
+ */
+const MAGIC: &[u8; 4] = b"NPDB";
+const VERSION: u32 = 1;
+const DEFAULT_CACHE: &str = ".pdb-cache";
+const UNSEEN: u8 = u8::MAX;
+
+/*
+ * How the tiles are split into disjoint patterns
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Partition {
+    Default,
+    Sizes(Vec<usize>),
+    Tiles(Vec<Vec<i64>>),
+}
+
+impl Partition {
+    /*
+     * "" for the default split, "6-6-3" for sizes taken in the goal
+     * reading order, "1,2,3/4,5,6/7,8" for explicit tiles
+     */
+    pub fn parse(s: &str) -> Option<Partition> {
+        if s.is_empty() {
+            Some(Partition::Default)
+        } else if s.contains(',') || s.contains('/') {
+            s.split('/')
+                .map(|p| p.split(',').map(|t| t.trim().parse::<i64>().ok()).collect())
+                .collect::<Option<Vec<Vec<i64>>>>()
+                .map(Partition::Tiles)
+        } else {
+            s.split('-')
+                .map(|n| n.trim().parse::<usize>().ok().filter(|&n| n > 0))
+                .collect::<Option<Vec<usize>>>()
+                .map(Partition::Sizes)
+        }
+    }
+
+    fn default_sizes(cells: usize) -> Vec<usize> {
+        match cells {
+            4 => vec![3],
+            9 => vec![4, 4],
+            16 => vec![6, 6, 3],
+            25 => vec![4, 4, 4, 4, 4, 4],
+            _ => vec![3; (cells - 1) / 3 + 1],
+        }
+    }
+
+    /*
+     * Tiles of every pattern, checked to cover each tile of the goal once
+     */
+    fn patterns(&self, goal: &[Vec<i64>]) -> Result<Vec<Vec<i64>>, String> {
+        let tiles: Vec<i64> = goal.iter().flatten().cloned().filter(|&t| t != 0).collect();
+        let patterns = match self {
+            Partition::Tiles(patterns) => patterns.clone(),
+            Partition::Default | Partition::Sizes(_) => {
+                let sizes = match self {
+                    Partition::Sizes(sizes) => sizes.clone(),
+                    _ => Self::default_sizes(tiles.len() + 1),
+                };
+                let mut rest = tiles.iter();
+                sizes
+                    .iter()
+                    .map(|&n| rest.by_ref().take(n).cloned().collect::<Vec<i64>>())
+                    .filter(|p| !p.is_empty())
+                    .collect()
+            }
+        };
+        let mut all: Vec<i64> = patterns.iter().flatten().cloned().collect();
+        all.sort();
+        let mut expected = tiles;
+        expected.sort();
+        if all != expected {
+            return Err(format!(
+                "the patterns {:?} do not cover the tiles {:?} exactly once",
+                patterns, expected
+            ));
+        }
+        Ok(patterns)
+    }
+}
+
+/*
+ * Additive disjoint pattern database: for each pattern, the minimal number
+ * of moves of its own tiles needed to bring them home, indexed by their
+ * positions (base cells number, one digit per tile)
+ */
+pub struct PatternDatabase {
+    rows: usize,
+    cols: usize,
+    goal: Vec<Vec<i64>>,
+    patterns: Vec<Vec<i64>>,
+    tables: Vec<Vec<u8>>,
+    slots: HashMap<i64, (usize, usize)>,
+    /* Where the tables come from: built, loaded or saved, and the path */
+    origin: String,
+}
+
+impl fmt::Debug for PatternDatabase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PatternDatabase({:?}, {})", self.patterns, self.origin)
+    }
+}
+
+impl PatternDatabase {
+    /*
+     * Load the database matching the goal and the partition from the
+     * cache directory, or build and save it
+     */
+    pub fn load_or_build(
+        goal: &[Vec<i64>],
+        partition: &Partition,
+    ) -> Result<PatternDatabase, NPuzzleError> {
+        let patterns = partition
+            .patterns(goal)
+            .map_err(NPuzzleError::InvalidPartition)?;
+        let cells = goal.len() * goal[0].len();
+        if let Some(pattern) = patterns
+            .iter()
+            .find(|p| Self::states(cells, p.len()).is_none())
+        {
+            return Err(NPuzzleError::PatternTooLarge(pattern.clone()));
+        }
+        let path = cache_path("pdb", VERSION, goal, &patterns);
+        if let Ok(Some(mut db)) = Self::load(&path, goal, &patterns) {
+            db.origin = format!("loaded from {}", path.display());
+            return Ok(db);
+        }
+        let mut db = Self::build(goal, patterns);
+        db.origin = match db.save(&path) {
+            Ok(_) => format!("built, saved to {}", path.display()),
+            Err(e) => format!("built, cannot save it: {}", e),
+        };
+        Ok(db)
+    }
+
+    /*
+     * Number of (pattern positions, blank) states, None beyond 2^32
+     */
+    fn states(cells: usize, tiles: usize) -> Option<usize> {
+        cells
+            .checked_pow(tiles as u32 + 1)
+            .filter(|&states| states <= 1 << 32)
+    }
+
+    pub fn build(goal: &[Vec<i64>], patterns: Vec<Vec<i64>>) -> PatternDatabase {
+        let tables = patterns
+            .par_iter()
+            .map(|pattern| Self::build_table(goal, pattern))
+            .collect();
+        Self::with_tables(goal, patterns, tables)
+    }
+
+    fn with_tables(
+        goal: &[Vec<i64>],
+        patterns: Vec<Vec<i64>>,
+        tables: Vec<Vec<u8>>,
+    ) -> PatternDatabase {
+        let slots = patterns
+            .iter()
+            .enumerate()
+            .flat_map(|(p, tiles)| tiles.iter().enumerate().map(move |(i, &t)| (t, (p, i))))
+            .collect();
+        PatternDatabase {
+            rows: goal.len(),
+            cols: goal[0].len(),
+            goal: goal.to_vec(),
+            patterns,
+            tables,
+            slots,
+            origin: "built".to_string(),
+        }
+    }
+
+    pub fn goal(&self) -> &[Vec<i64>] {
+        &self.goal
+    }
+
+    /*
+     * Backward 0-1 BFS from the goal over (pattern positions, blank): moving
+     * another tile is free, moving a pattern tile costs one
+     */
+    fn build_table(goal: &[Vec<i64>], pattern: &[i64]) -> Vec<u8> {
+        let (rows, cols) = (goal.len(), goal[0].len());
+        let cells = rows * cols;
+        let k = pattern.len();
+        let pow: Vec<usize> = (0..=k).map(|i| cells.pow(i as u32)).collect();
+        let states = Self::states(cells, k).expect("pattern too large for this board");
+        let flat: Vec<i64> = goal.iter().flatten().cloned().collect();
+        let position = |tile: i64| flat.iter().position(|&t| t == tile).unwrap();
+        let start: usize = pattern
+            .iter()
+            .enumerate()
+            .map(|(i, &t)| position(t) * pow[i])
+            .sum::<usize>()
+            + position(0) * pow[k];
+
+        let mut table = vec![UNSEEN; pow[k]];
+        let mut visited = vec![0u64; states / 64 + 1];
+        let mut queued = vec![0u64; states / 64 + 1];
+        let test = |bits: &[u64], s: usize| bits[s / 64] & (1 << (s % 64)) != 0;
+        let set = |bits: &mut [u64], s: usize| bits[s / 64] |= 1 << (s % 64);
+
+        let mut layer: Vec<usize> = vec![start];
+        let mut depth: u8 = 0;
+        let mut positions = vec![0usize; k];
+        while !layer.is_empty() {
+            let mut next: Vec<usize> = vec![];
+            while let Some(state) = layer.pop() {
+                if test(&visited, state) {
+                    continue;
+                }
+                set(&mut visited, state);
+                let index = state % pow[k];
+                let blank = state / pow[k];
+                if table[index] == UNSEEN {
+                    table[index] = depth;
+                }
+                for (i, p) in positions.iter_mut().enumerate() {
+                    *p = index / pow[i] % cells;
+                }
+                let (x, y) = (blank / cols, blank % cols);
+                let around = [
+                    (x > 0, blank.wrapping_sub(cols)),
+                    (x + 1 < rows, blank + cols),
+                    (y > 0, blank.wrapping_sub(1)),
+                    (y + 1 < cols, blank + 1),
+                ];
+                for &(_, cell) in around.iter().filter(|(ok, _)| *ok) {
+                    match positions.iter().position(|&p| p == cell) {
+                        Some(i) => {
+                            let moved = index - cell * pow[i] + blank * pow[i] + cell * pow[k];
+                            if !test(&visited, moved) && !test(&queued, moved) {
+                                set(&mut queued, moved);
+                                next.push(moved);
+                            }
+                        }
+                        None => {
+                            let moved = index + cell * pow[k];
+                            if !test(&visited, moved) {
+                                layer.push(moved);
+                            }
+                        }
+                    }
+                }
+            }
+            layer = next;
+            depth = depth.saturating_add(1).min(UNSEEN - 1);
+        }
+        table
+    }
+
+    /*
+     * Index of every pattern for the grid
+     */
+    fn lookup(&self, grid: &Board) -> Vec<usize> {
+        let cells = self.rows * self.cols;
+        let mut index = vec![0usize; self.patterns.len()];
+        for (c, tile) in grid.tiles().enumerate() {
+            if let Some(&(p, slot)) = self.slots.get(&tile) {
+                index[p] += c * cells.pow(slot as u32);
+            }
+        }
+        index
+    }
+
+    fn entry(&self, pattern: usize, index: usize) -> f64 {
+        match self.tables[pattern][index] {
+            UNSEEN => 0f64,
+            v => v as f64,
+        }
+    }
+
+    pub fn value(&self, grid: &Board) -> f64 {
+        self.lookup(grid)
+            .into_iter()
+            .enumerate()
+            .map(|(p, index)| self.entry(p, index))
+            .sum()
+    }
+
+    /*
+     * Change of the value when the tile at `tile` slides into the empty
+     * space at `blank`: only the pattern holding it changes, its index by
+     * the tile's own digit
+     */
+    pub fn value_delta(&self, parent: &Board, blank: (usize, usize), tile: (usize, usize)) -> f64 {
+        let &(pattern, slot) = match self.slots.get(&parent.at(tile)) {
+            Some(slot) => slot,
+            None => return 0f64,
+        };
+        let before = self.lookup(parent)[pattern];
+        let digit = (self.rows * self.cols).pow(slot as u32);
+        let (from, to) = (tile.0 * self.cols + tile.1, blank.0 * self.cols + blank.1);
+        let after = before - from * digit + to * digit;
+        self.entry(pattern, after) - self.entry(pattern, before)
+    }
+
+    fn header(goal: &[Vec<i64>], patterns: &[Vec<i64>]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(&VERSION.to_le_bytes());
+        bytes.extend(&(goal.len() as u32).to_le_bytes());
+        for tile in goal.iter().flatten() {
+            bytes.extend(&tile.to_le_bytes());
+        }
+        bytes.extend(&(patterns.len() as u32).to_le_bytes());
+        for pattern in patterns {
+            bytes.extend(&(pattern.len() as u32).to_le_bytes());
+            for tile in pattern {
+                bytes.extend(&tile.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    fn save(&self, path: &PathBuf) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut bytes = Self::header(&self.goal, &self.patterns);
+        for table in self.tables.iter() {
+            bytes.extend(&(table.len() as u64).to_le_bytes());
+            bytes.extend(table);
+        }
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(&tmp, path)
+    }
+
+    /*
+     * None when the file was written for another version, goal or partition,
+     * or when a table does not have one entry per placement of its pattern
+     */
+    fn load(
+        path: &PathBuf,
+        goal: &[Vec<i64>],
+        patterns: &[Vec<i64>],
+    ) -> io::Result<Option<PatternDatabase>> {
+        let bytes = fs::read(path)?;
+        let header = Self::header(goal, patterns);
+        if !bytes.starts_with(&header) {
+            return Ok(None);
+        }
+        let mut rest = &bytes[header.len()..];
+        let mut tables = vec![];
+        for _ in patterns {
+            if rest.len() < 8 {
+                return Ok(None);
+            }
+            let mut len = [0u8; 8];
+            len.copy_from_slice(&rest[..8]);
+            let len = u64::from_le_bytes(len) as usize;
+            if rest.len() < 8 + len {
+                return Ok(None);
+            }
+            tables.push(rest[8..8 + len].to_vec());
+            rest = &rest[8 + len..];
+        }
+        let cells = goal.len() * goal[0].len();
+        if !rest.is_empty()
+            || patterns.iter().zip(tables.iter()).any(|(pattern, table)| {
+                Some(table.len()) != cells.checked_pow(pattern.len() as u32)
+            })
+        {
+            return Ok(None);
+        }
+        Ok(Some(Self::with_tables(goal, patterns.to_vec(), tables)))
+    }
+}
+
+/*
+ * File of the cache directory for a table of the given kind, named after
+ * the goal and the patterns it was built for
+ */
+pub(crate) fn cache_path(
+    kind: &str,
+    version: u32,
+    goal: &[Vec<i64>],
+    patterns: &[Vec<i64>],
+) -> PathBuf {
+    // FNV-1a, stable between runs and builds
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let words = goal
+        .iter()
+        .flatten()
+        .chain([-1].iter())
+        .chain(patterns.iter().flat_map(|p| p.iter().chain([-1].iter())));
+    for word in words {
+        for byte in word.to_le_bytes().iter() {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    let dir = env::var("NPUZZLE_PDB_CACHE").unwrap_or_else(|_| DEFAULT_CACHE.to_string());
+    PathBuf::from(dir).join(format!(
+        "{}-v{}-{}x{}-{:016x}.bin",
+        kind,
+        version,
+        goal.len(),
+        goal[0].len(),
+        hash
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_pattern_goal() {
+        let goal = &[vec![1, 2, 3], vec![8, 0, 4], vec![7, 6, 5]];
+        let db = PatternDatabase::build(goal, vec![vec![1, 2, 3, 8], vec![4, 5, 6, 7]]);
+        assert_eq!(0f64, db.value(&Board::new(goal).unwrap()));
+    }
+    #[test]
+    fn test_pattern_admissible() {
+        // 1 2 3 / 8 0 4 / 7 6 5 is 13 moves away from this grid
+        let goal = &[vec![1, 2, 3], vec![8, 0, 4], vec![7, 6, 5]];
+        let grid = &Board::new(&[vec![8, 3, 4], vec![2, 6, 5], vec![1, 0, 7]]).unwrap();
+        let db = PatternDatabase::build(goal, vec![vec![1, 2, 3, 8], vec![4, 5, 6, 7]]);
+        let h = db.value(grid);
+        assert!((10f64..=13f64).contains(&h));
+    }
+    #[test]
+    fn test_pattern_cache() {
+        let goal = &[vec![1, 2, 3], vec![8, 0, 4], vec![7, 6, 5]];
+        let patterns = vec![vec![1, 2, 3, 8], vec![4, 5, 6, 7]];
+        let db = PatternDatabase::build(goal, patterns.clone());
+        let path = env::temp_dir().join(format!("npuzzle-pdb-test-{}.bin", std::process::id()));
+        db.save(&path).unwrap();
+        assert!(PatternDatabase::load(&path, goal, &patterns)
+            .unwrap()
+            .is_some());
+        // A shorter last table, its length prefix edited to match
+        let mut bytes = fs::read(&path).unwrap();
+        let len = bytes.len() - 9 * 9 * 9 * 9 - 8;
+        bytes.truncate(len);
+        bytes.extend(&10u64.to_le_bytes());
+        bytes.extend(&[0u8; 10]);
+        fs::write(&path, bytes).unwrap();
+        assert!(PatternDatabase::load(&path, goal, &patterns)
+            .unwrap()
+            .is_none());
+        fs::remove_file(&path).unwrap();
+    }
+    #[test]
+    fn test_partition_parse() {
+        assert_eq!(
+            Some(Partition::Sizes(vec![6, 6, 3])),
+            Partition::parse("6-6-3")
+        );
+        assert_eq!(
+            Some(Partition::Tiles(vec![vec![1, 2], vec![3]])),
+            Partition::parse("1,2/3")
+        );
+        assert_eq!(None, Partition::parse("6-a"));
+    }
+}
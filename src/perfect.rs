@@ -0,0 +1,273 @@
+use super::board::*;
+use super::error::*;
+use super::generate::*;
+use super::pattern::cache_path;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/*
+ * Boards up to this many cells are enumerated (9! states)
+ */
+pub const MAX_CELLS: usize = 9;
+
+/*
+ * Binary cache layout, bump VERSION whenever it changes
+ */
+const MAGIC: &[u8; 4] = b"NPPT";
+const VERSION: u32 = 1;
+const UNSEEN: u8 = u8::MAX;
+
+/*
+ * Optimal number of moves to the goal of every configuration of a small
+ * board, indexed by the rank of its permutation: the goal cell of the tile
+ * standing on each cell. The configurations of the other parity cannot
+ * reach the goal and stay UNSEEN.
+ */
+pub struct PerfectTable {
+    rows: usize,
+    cols: usize,
+    goal: Vec<Vec<i64>>,
+    /* Goal cell of every tile, indexed by tile */
+    home: Vec<u8>,
+    table: Vec<u8>,
+    /* Where the table comes from: built, loaded or saved, and the path */
+    origin: String,
+}
+
+impl fmt::Debug for PerfectTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PerfectTable({}x{}, {})",
+            self.rows, self.cols, self.origin
+        )
+    }
+}
+
+impl PerfectTable {
+    /*
+     * Load the table of the goal from the cache directory, or build and
+     * save it
+     */
+    pub fn load_or_build(goal: &[Vec<i64>]) -> Result<PerfectTable, NPuzzleError> {
+        let path = cache_path("perfect", VERSION, goal, &[]);
+        if let Ok(Some(mut table)) = Self::load(&path, goal) {
+            table.origin = format!("loaded from {}", path.display());
+            return Ok(table);
+        }
+        let mut table = Self::build(goal)?;
+        table.origin = match table.save(&path) {
+            Ok(_) => format!("built, saved to {}", path.display()),
+            Err(e) => format!("built, cannot save it: {}", e),
+        };
+        Ok(table)
+    }
+
+    /*
+     * Breadth-first search backwards from the goal: the first time a
+     * configuration is reached gives its optimal distance
+     */
+    pub fn build(goal: &[Vec<i64>]) -> Result<PerfectTable, NPuzzleError> {
+        let mut res = Self::with_table(goal, vec![])?;
+        let (rows, cols) = (res.rows, res.cols);
+        let cells = rows * cols;
+        let blank = res.home[0];
+        res.table = vec![UNSEEN; (1..=cells).product()];
+        // The goal itself is the identity, of rank 0
+        res.table[0] = 0;
+        let mut queue = VecDeque::from(vec![0]);
+        while let Some(state) = queue.pop_front() {
+            let perm = unrank(state, cells);
+            let from = perm.iter().position(|&p| p == blank).unwrap();
+            for (i, j) in neighbours((rows as i64, cols as i64), (from / cols, from % cols), None) {
+                let mut next = perm.clone();
+                next.swap(from, i * cols + j);
+                let next = rank(&next);
+                if res.table[next] == UNSEEN {
+                    res.table[next] = res.table[state] + 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /*
+     * A table without distances, once the goal is checked to be small
+     * enough and numbered from 0
+     */
+    fn with_table(goal: &[Vec<i64>], table: Vec<u8>) -> Result<PerfectTable, NPuzzleError> {
+        let (rows, cols) = (goal.len(), goal.first().map_or(0, |row| row.len()));
+        if rows * cols > MAX_CELLS {
+            return Err(NPuzzleError::TooManyStates(rows as i64, cols as i64));
+        }
+        let mut tiles: Vec<i64> = goal.iter().flatten().cloned().collect();
+        tiles.sort();
+        if Board::new(goal).is_none() || !tiles.iter().cloned().eq(0..tiles.len() as i64) {
+            return Err(NPuzzleError::GoalMismatch);
+        }
+        let mut home = vec![0u8; rows * cols];
+        for (c, &tile) in goal.iter().flatten().enumerate() {
+            home[tile as usize] = c as u8;
+        }
+        Ok(PerfectTable {
+            rows,
+            cols,
+            goal: goal.to_vec(),
+            home,
+            table,
+            origin: "built".to_string(),
+        })
+    }
+
+    pub fn goal(&self) -> &[Vec<i64>] {
+        &self.goal
+    }
+
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    /*
+     * Optimal number of moves to the goal, None when it cannot be reached
+     */
+    pub fn distance(&self, grid: &Board) -> Option<u8> {
+        if grid.rows() != self.rows || grid.cols() != self.cols {
+            return None;
+        }
+        let perm: Vec<u8> = grid.tiles().map(|t| self.home[t as usize]).collect();
+        match self.table[rank(&perm)] {
+            UNSEEN => None,
+            d => Some(d),
+        }
+    }
+
+    /*
+     * Number of configurations at each distance, from 0 to the largest
+     */
+    pub fn histogram(&self) -> Vec<usize> {
+        let mut res = vec![];
+        for &d in self.table.iter().filter(|&&d| d != UNSEEN) {
+            if res.len() <= d as usize {
+                res.resize(d as usize + 1, 0);
+            }
+            res[d as usize] += 1;
+        }
+        res
+    }
+
+    /*
+     * Every configuration exactly distance moves away from the goal
+     */
+    pub fn at_distance(&self, distance: usize) -> impl Iterator<Item = Vec<Vec<i64>>> + '_ {
+        let tiles: Vec<i64> = self.goal.iter().flatten().cloned().collect();
+        self.table
+            .iter()
+            .enumerate()
+            .filter(move |&(_, &d)| d as usize == distance)
+            .map(move |(state, _)| {
+                unrank(state, tiles.len())
+                    .chunks(self.cols)
+                    .map(|row| row.iter().map(|&p| tiles[p as usize]).collect())
+                    .collect()
+            })
+    }
+
+    /*
+     * Distance to the goal, 0 for a grid which cannot reach it
+     */
+    pub fn value(&self, grid: &Board) -> f64 {
+        self.distance(grid).map_or(0f64, |d| d as f64)
+    }
+
+    fn header(goal: &[Vec<i64>]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(&VERSION.to_le_bytes());
+        bytes.extend(&(goal.len() as u32).to_le_bytes());
+        for tile in goal.iter().flatten() {
+            bytes.extend(&tile.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut bytes = Self::header(&self.goal);
+        bytes.extend(&self.table);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(&tmp, path)
+    }
+
+    /*
+     * None when the file was written for another version or goal
+     */
+    pub fn load(path: &Path, goal: &[Vec<i64>]) -> io::Result<Option<PerfectTable>> {
+        let bytes = fs::read(path)?;
+        let header = Self::header(goal);
+        if !bytes.starts_with(&header) {
+            return Ok(None);
+        }
+        let table = bytes[header.len()..].to_vec();
+        match Self::with_table(goal, table) {
+            Ok(table) if table.table.len() == (1..=table.rows * table.cols).product::<usize>() => {
+                Ok(Some(table))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/*
+ * Position of the permutation in lexicographic order (Lehmer code)
+ */
+fn rank(perm: &[u8]) -> usize {
+    let n = perm.len();
+    (0..n).fold(0, |rank, i| {
+        rank * (n - i) + perm[i + 1..].iter().filter(|&&p| p < perm[i]).count()
+    })
+}
+
+fn unrank(mut rank: usize, n: usize) -> Vec<u8> {
+    let mut digits = vec![0; n];
+    for i in (0..n).rev() {
+        digits[i] = rank % (n - i);
+        rank /= n - i;
+    }
+    let mut left: Vec<u8> = (0..n as u8).collect();
+    digits.iter().map(|&d| left.remove(d)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_perfect_table() {
+        assert_eq!(vec![2, 0, 1], unrank(rank(&[2, 0, 1]), 3));
+        let goal = &[vec![1, 2, 3], vec![4, 5, 0]];
+        let table = PerfectTable::build(goal).unwrap();
+        let histogram = table.histogram();
+        // Half of the 6! arrangements, the farthest 21 moves away
+        assert_eq!(360, histogram.iter().sum::<usize>());
+        assert_eq!(22, histogram.len());
+        assert_eq!(1, histogram[0]);
+        let board = |grid: &[Vec<i64>]| Board::new(grid).unwrap();
+        assert_eq!(
+            Some(1),
+            table.distance(&board(&[vec![1, 2, 3], vec![4, 0, 5]]))
+        );
+        assert_eq!(
+            None,
+            table.distance(&board(&[vec![2, 1, 3], vec![4, 5, 0]]))
+        );
+        for grid in table.at_distance(21) {
+            assert_eq!(Some(21), table.distance(&board(&grid)));
+        }
+        assert!(PerfectTable::build(&[vec![1, 2, 3, 4, 5], vec![6, 7, 8, 9, 0]]).is_err());
+    }
+}
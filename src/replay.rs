@@ -0,0 +1,108 @@
+use super::board::*;
+use super::error::*;
+use super::solution::*;
+
+/*
+ * Blank directions of a move string ("RDLU", spaces and commas ignored)
+ * written in the given convention
+ */
+pub fn parse_moves(s: &str, convention: Convention) -> Result<Vec<Direction>, NPuzzleError> {
+    s.chars()
+        .filter(|c| !c.is_whitespace() && *c != ',')
+        .map(|c| match (Direction::parse(c), convention) {
+            (Some(d), Convention::Blank) => Ok(d),
+            (Some(d), Convention::Tile) => Ok(d.opposite()),
+            (None, _) => Err(NPuzzleError::InvalidMove(c.to_string())),
+        })
+        .collect()
+}
+
+/*
+ * The board once the blank went one cell in direction, None when it
+ * would leave the board
+ */
+pub fn step(board: &Board, direction: Direction) -> Option<Board> {
+    let (i, j) = board.blank();
+    let cell = match direction {
+        Direction::Up if i > 0 => (i - 1, j),
+        Direction::Down if i + 1 < board.rows() => (i + 1, j),
+        Direction::Left if j > 0 => (i, j - 1),
+        Direction::Right if j + 1 < board.cols() => (i, j + 1),
+        _ => return None,
+    };
+    Some(board.slide(cell))
+}
+
+/*
+ * The board once tile slid into the empty space, with the direction the
+ * blank went, None when the tile is not next to it
+ */
+pub fn slide_tile(board: &Board, tile: i64) -> Option<(Board, Direction)> {
+    let (i, j) = board.blank();
+    let (a, b) = board.position(tile).filter(|_| tile != 0)?;
+    let direction = match (a as i64 - i as i64, b as i64 - j as i64) {
+        (-1, 0) => Direction::Up,
+        (1, 0) => Direction::Down,
+        (0, -1) => Direction::Left,
+        (0, 1) => Direction::Right,
+        _ => return None,
+    };
+    Some((board.slide((a, b)), direction))
+}
+
+/*
+ * What a move string does to a puzzle
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    /* Every board reached, the initial one first */
+    pub path: Vec<Board>,
+    /* First move leaving the board, with its index, after which the replay stops */
+    pub illegal: Option<(usize, Direction)>,
+    pub solved: bool,
+}
+
+pub fn replay(initial: &Board, goal: &Board, moves: &[Direction]) -> Replay {
+    let mut path = vec![initial.clone()];
+    let mut illegal = None;
+    for (n, &direction) in moves.iter().enumerate() {
+        match step(path.last().unwrap(), direction) {
+            Some(next) => path.push(next),
+            None => {
+                illegal = Some((n, direction));
+                break;
+            }
+        }
+    }
+    let solved = illegal.is_none() && path.last() == Some(goal);
+    Replay {
+        path,
+        illegal,
+        solved,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_replay() {
+        let board = |grid: &[Vec<i64>]| Board::new(grid).unwrap();
+        let initial = board(&[vec![1, 2, 3], vec![4, 0, 5]]);
+        let goal = board(&[vec![1, 2, 3], vec![4, 5, 0]]);
+        let moves = parse_moves("r", Convention::Blank).unwrap();
+        assert!(replay(&initial, &goal, &moves).solved);
+        let moves = parse_moves("L", Convention::Tile).unwrap();
+        assert!(replay(&initial, &goal, &moves).solved);
+        let moves = parse_moves("R, L, D", Convention::Blank).unwrap();
+        let res = replay(&initial, &goal, &moves);
+        assert_eq!(Some((2, Direction::Down)), res.illegal);
+        assert_eq!(3, res.path.len());
+        assert!(parse_moves("RX", Convention::Blank).is_err());
+        assert_eq!(
+            Some((goal.clone(), Direction::Right)),
+            slide_tile(&initial, 5)
+        );
+        assert_eq!(None, slide_tile(&initial, 1));
+    }
+}
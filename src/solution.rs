@@ -0,0 +1,312 @@
+use super::error::*;
+use super::node::*;
+use super::Stop;
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/*
+ * Where the blank goes
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    /*
+     * U, D, L or R, in either case
+     */
+    pub fn parse(c: char) -> Option<Direction> {
+        match c.to_ascii_uppercase() {
+            'U' => Some(Direction::Up),
+            'D' => Some(Direction::Down),
+            'L' => Some(Direction::Left),
+            'R' => Some(Direction::Right),
+            _ => None,
+        }
+    }
+
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Direction::Up => write!(f, "U"),
+            Direction::Down => write!(f, "D"),
+            Direction::Left => write!(f, "L"),
+            Direction::Right => write!(f, "R"),
+        }
+    }
+}
+
+/*
+ * Whose move the letters describe: the blank's, or the sliding tile's
+ * (always the opposite direction)
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Convention {
+    Blank,
+    Tile,
+}
+
+impl Convention {
+    pub fn parse(s: String) -> Result<Convention, NPuzzleError> {
+        Ok(match &s.to_lowercase() as &str {
+            "blank" => Convention::Blank,
+            "tile" => Convention::Tile,
+            _ => return Err(NPuzzleError::UnknownConvention(s)),
+        })
+    }
+}
+
+/*
+ * What is printed for a path: the move string, every grid, or both
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Output {
+    Moves,
+    Grids,
+    Both,
+}
+
+impl Output {
+    pub fn parse(s: String) -> Result<Output, NPuzzleError> {
+        Ok(match &s.to_lowercase() as &str {
+            "moves" => Output::Moves,
+            "grids" => Output::Grids,
+            "both" => Output::Both,
+            _ => return Err(NPuzzleError::UnknownOutput(s)),
+        })
+    }
+}
+
+/*
+ * One step of the path: the blank moves, the tile takes its place
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Move {
+    pub direction: Direction,
+    pub tile: i64,
+}
+
+impl Move {
+    pub fn letter(&self, convention: Convention) -> Direction {
+        match convention {
+            Convention::Blank => self.direction,
+            Convention::Tile => self.direction.opposite(),
+        }
+    }
+}
+
+/*
+ * A path published by ARA*, with its guaranteed suboptimality
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Improvement {
+    pub weight: f64,
+    pub moves: f64,
+    pub bound: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub iterations: u64,
+    pub max_states: usize,
+    /* IDA* only: the threshold of every pass */
+    pub thresholds: Vec<f64>,
+    /* ARA* only */
+    pub improvements: Vec<Improvement>,
+    pub elapsed: Duration,
+}
+
+/*
+ * Result of a run: the path to the goal, or to the closest state (lowest h)
+ * when the search stopped before finding one
+ */
+#[derive(Debug, Clone)]
+pub struct Solution {
+    pub solved: bool,
+    /* Why the search stopped early, if it did */
+    pub stop: Option<Stop>,
+    pub path: Vec<Vec<Vec<i64>>>,
+    pub moves: Vec<Move>,
+    pub cost: f64,
+    /* h of the last grid of the path */
+    pub h: f64,
+    /* The cost is at most bound times the optimal one */
+    pub bound: Option<f64>,
+    pub stats: Stats,
+}
+
+impl Solution {
+    pub fn new(
+        last: &Arc<Node>,
+        solved: bool,
+        stop: Option<Stop>,
+        bound: Option<f64>,
+        stats: Stats,
+    ) -> Solution {
+        let mut path = vec![];
+        let mut cur = Some(last.clone());
+        while let Some(node) = cur {
+            path.push(node.grid.grid());
+            cur = node.parent.clone();
+        }
+        path.reverse();
+        let moves = path.windows(2).map(|w| Self::step(&w[0], &w[1])).collect();
+        Solution {
+            solved,
+            stop,
+            path,
+            moves,
+            cost: last.g,
+            h: last.h,
+            bound,
+            stats,
+        }
+    }
+
+    /*
+     * Compact move string, "ULDR..."
+     */
+    pub fn notation(&self, convention: Convention) -> String {
+        self.moves
+            .iter()
+            .map(|m| m.letter(convention).to_string())
+            .collect()
+    }
+
+    /*
+     * Number of every tile moved, in order
+     */
+    pub fn tiles(&self) -> Vec<i64> {
+        self.moves.iter().map(|m| m.tile).collect()
+    }
+
+    fn step(before: &[Vec<i64>], after: &[Vec<i64>]) -> Move {
+        let blank = |grid: &[Vec<i64>]| {
+            grid.iter()
+                .enumerate()
+                .find_map(|(i, row)| row.iter().position(|&t| t == 0).map(|j| (i, j)))
+                .unwrap()
+        };
+        let ((x, y), (a, b)) = (blank(before), blank(after));
+        let direction = match (a.cmp(&x), b.cmp(&y)) {
+            (Ordering::Less, _) => Direction::Up,
+            (Ordering::Greater, _) => Direction::Down,
+            (_, Ordering::Less) => Direction::Left,
+            _ => Direction::Right,
+        };
+        Move {
+            direction,
+            tile: before[a][b],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::*;
+    #[test]
+    fn test_moves() {
+        let grid = |g: &[Vec<i64>]| Board::new(g).unwrap();
+        let root = Arc::new(Node {
+            grid: grid(&[vec![1, 2, 3], vec![8, 0, 4], vec![7, 6, 5]]),
+            hash: 0,
+            f: 0.0,
+            g: 0.0,
+            h: 0.0,
+            parent: None,
+        });
+        let up = Arc::new(Node {
+            grid: root.grid.slide((0, 1)),
+            g: 1.0,
+            parent: Some(root.clone()),
+            ..(*root).clone()
+        });
+        let right = Arc::new(Node {
+            grid: up.grid.slide((0, 2)),
+            g: 2.0,
+            parent: Some(up.clone()),
+            ..(*root).clone()
+        });
+        let solution = Solution::new(&right, true, None, None, Stats::default());
+        assert_eq!(3, solution.path.len());
+        assert_eq!(
+            vec![
+                Move {
+                    direction: Direction::Up,
+                    tile: 2
+                },
+                Move {
+                    direction: Direction::Right,
+                    tile: 3
+                }
+            ],
+            solution.moves
+        );
+        assert_eq!(2.0, solution.cost);
+        assert_eq!("UR", solution.notation(Convention::Blank));
+        assert_eq!("DL", solution.notation(Convention::Tile));
+        assert_eq!(vec![2, 3], solution.tiles());
+    }
+    #[test]
+    fn test_notation_conventions() {
+        let mut node = Arc::new(Node {
+            grid: Board::new(&[vec![1, 2, 3], vec![8, 0, 4], vec![7, 6, 5]]).unwrap(),
+            hash: 0,
+            f: 0.0,
+            g: 0.0,
+            h: 0.0,
+            parent: None,
+        });
+        // The blank goes up, left, down, down, right, right, up
+        for &cell in [(0, 1), (0, 0), (1, 0), (2, 0), (2, 1), (2, 2), (1, 2)].iter() {
+            node = Arc::new(Node {
+                grid: node.grid.slide(cell),
+                g: node.g + 1.0,
+                parent: Some(node.clone()),
+                ..(*node).clone()
+            });
+        }
+        let solution = Solution::new(&node, true, None, None, Stats::default());
+        let blank = solution.notation(Convention::Blank);
+        let tile = solution.notation(Convention::Tile);
+        assert_eq!("ULDDRRU", blank);
+        assert_eq!("DRUULLD", tile);
+        let inverse: String = solution
+            .moves
+            .iter()
+            .map(|m| m.letter(Convention::Tile).opposite().to_string())
+            .collect();
+        assert_eq!(blank, inverse);
+    }
+    #[test]
+    fn test_parse_output() {
+        assert_eq!(Output::Moves, Output::parse("moves".to_string()).unwrap());
+        assert_eq!(Output::Both, Output::parse("BOTH".to_string()).unwrap());
+        assert!(matches!(
+            Output::parse("grid".to_string()),
+            Err(NPuzzleError::UnknownOutput(s)) if s == "grid"
+        ));
+        assert_eq!(
+            Convention::Tile,
+            Convention::parse("tile".to_string()).unwrap()
+        );
+        assert!(Convention::parse("blanks".to_string()).is_err());
+    }
+}
@@ -1,8 +1,8 @@
+use super::board::*;
+use super::error::*;
 use super::heuristique::*;
-use super::utils::*;
-use std::process;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Strategy {
     Standard(Heuristique),
     Sandwich(Vec<Vec<f64>>, Heuristique),
@@ -10,38 +10,56 @@ pub enum Strategy {
 }
 
 impl Strategy {
-    pub fn parse(s: String, heuristique: Heuristique) -> Strategy {
-        match &s.to_lowercase() as &str {
+    pub fn parse(s: String, heuristique: Heuristique) -> Result<Strategy, NPuzzleError> {
+        Ok(match &s.to_lowercase() as &str {
             "standard" | "std" => Strategy::Standard(heuristique),
+            "sandwich" | "cross" if heuristique.whole_grid() => {
+                return Err(NPuzzleError::TiledStrategy(s))
+            }
             "sandwich" => Strategy::Sandwich(vec![], heuristique),
             "cross" => Strategy::Cross(vec![], heuristique),
-            _ => {
-                println!("Strategy not recognized");
-                process::exit(1);
-            }
+            _ => return Err(NPuzzleError::UnknownStrategy(s)),
+        })
+    }
+
+    /*
+     * The strategy of a search towards another target than the goal, which
+     * builds no table for it
+     */
+    pub fn without_table(&self) -> Strategy {
+        match self {
+            Strategy::Standard(h) => Strategy::Standard(h.without_table()),
+            Strategy::Sandwich(_, h) => Strategy::Sandwich(vec![], h.without_table()),
+            Strategy::Cross(_, h) => Strategy::Cross(vec![], h.without_table()),
         }
     }
 
     /*
      *	Init the Strategy
      */
-    pub fn init(&mut self, goal: &Vec<Vec<i64>>) {
+    pub fn init(&mut self, goal: &Board) -> Result<(), NPuzzleError> {
+        match self {
+            Strategy::Standard(heuristique)
+            | Strategy::Sandwich(_, heuristique)
+            | Strategy::Cross(_, heuristique) => heuristique.init(goal)?,
+        };
         match self {
             Strategy::Standard(_) => {}
             Strategy::Sandwich(weight, _) => Self::init_sandwich(weight, goal),
             Strategy::Cross(weight, _) => Self::init_cross(weight, goal),
         };
+        Ok(())
     }
 
     /*
      *	Init the sandwich strategy
      */
-    fn init_sandwich(weight: &mut Vec<Vec<f64>>, goal: &Vec<Vec<i64>>) {
+    fn init_sandwich(weight: &mut Vec<Vec<f64>>, goal: &Board) {
         *weight = Vec::new();
-        let (x, y) = find_nb(0, goal);
-        for i in 0..goal.len() {
+        let (x, y) = goal.blank();
+        for i in 0..goal.rows() {
             let mut new_grid = Vec::new();
-            for j in 0..goal.len() {
+            for j in 0..goal.cols() {
                 let max = (i as f64 - x as f64).abs().max((j as f64 - y as f64).abs());
                 new_grid.push(max + 1f64);
             }
@@ -49,12 +67,12 @@ impl Strategy {
         }
     }
 
-    fn init_cross(weight: &mut Vec<Vec<f64>>, goal: &Vec<Vec<i64>>) {
+    fn init_cross(weight: &mut Vec<Vec<f64>>, goal: &Board) {
         *weight = Vec::new();
-        let (x, y) = find_nb(0, goal);
-        for i in 0..goal.len() {
+        let (x, y) = goal.blank();
+        for i in 0..goal.rows() {
             let mut new_grid = Vec::new();
-            for j in 0..goal.len() {
+            for j in 0..goal.cols() {
                 let max = (i as f64 - x as f64).abs() + (j as f64 - y as f64).abs();
                 new_grid.push(max + 1f64);
             }
@@ -62,7 +80,7 @@ impl Strategy {
         }
     }
 
-    pub fn process(&self, current: &Vec<Vec<i64>>, goal: &Vec<Vec<i64>>) -> f64 {
+    pub fn process(&self, current: &Board, goal: &Board) -> f64 {
         match self {
             Strategy::Standard(heuristique) => Self::process_std(heuristique, current, goal),
             Strategy::Sandwich(weight, heuristique) => {
@@ -74,13 +92,37 @@ impl Strategy {
         }
     }
 
-    fn process_std(
-        heuristique: &Heuristique,
-        current: &Vec<Vec<i64>>,
-        goal: &Vec<Vec<i64>>,
+    /*
+     * h of the grid obtained by sliding the tile at `tile` into the empty
+     * space at `blank`, from the h of the parent grid and the homes of the goal
+     */
+    pub fn process_delta(
+        &self,
+        parent_h: f64,
+        parent: &Board,
+        current: &Board,
+        homes: &Homes,
+        (blank, tile): ((usize, usize), (usize, usize)),
     ) -> f64 {
+        let (weight, heuristique, power) = match self {
+            Strategy::Standard(heuristique) => (None, heuristique, 0),
+            Strategy::Sandwich(weight, heuristique) => (Some(weight), heuristique, 2),
+            Strategy::Cross(weight, heuristique) => (Some(weight), heuristique, 1),
+        };
+        parent_h
+            + heuristique
+                .process_delta(parent, current, homes, blank, tile)
+                .iter()
+                .map(|&((i, j), d)| match weight {
+                    Some(w) => d * w[i][j].powi(power),
+                    None => d,
+                })
+                .sum::<f64>()
+    }
+
+    fn process_std(heuristique: &Heuristique, current: &Board, goal: &Board) -> f64 {
         heuristique
-            .process_h(current, &goal)
+            .process_h(current, goal)
             .iter()
             .map(|x| x.iter().sum())
             .collect::<Vec<f64>>()
@@ -89,13 +131,13 @@ impl Strategy {
     }
 
     fn process_sandwich(
-        weight: &Vec<Vec<f64>>,
+        weight: &[Vec<f64>],
         heuristique: &Heuristique,
-        current: &Vec<Vec<i64>>,
-        goal: &Vec<Vec<i64>>,
+        current: &Board,
+        goal: &Board,
     ) -> f64 {
         heuristique
-            .process_h(current, &goal)
+            .process_h(current, goal)
             .iter()
             .enumerate()
             .map(|(i, x)| {
@@ -110,13 +152,13 @@ impl Strategy {
     }
 
     fn process_cross(
-        weight: &Vec<Vec<f64>>,
+        weight: &[Vec<f64>],
         heuristique: &Heuristique,
-        current: &Vec<Vec<i64>>,
-        goal: &Vec<Vec<i64>>,
+        current: &Board,
+        goal: &Board,
     ) -> f64 {
         heuristique
-            .process_h(current, &goal)
+            .process_h(current, goal)
             .iter()
             .enumerate()
             .map(|(i, x)| x.iter().enumerate().map(|(j, y)| y * weight[i][j]).sum())
@@ -128,14 +170,75 @@ impl Strategy {
 
 #[cfg(test)]
 mod tests {
+    use super::super::pattern::*;
+    use super::super::perfect::*;
     use super::*;
+    use std::sync::Arc;
+
+    /*
+     * Follow a pseudo-random walk, checking the h computed move by move
+     * against the h computed on the whole grid
+     */
+    fn check_incremental(mut strategy: Strategy, goal: &Board) {
+        strategy.init(goal).unwrap();
+        let mut grid = goal.clone();
+        let mut h = strategy.process(&grid, goal);
+        let homes = &Homes::new(goal);
+        let mut seed: u64 = 42;
+        for _ in 0..300 {
+            let blank = grid.blank();
+            let (x, y) = (blank.0 as i64, blank.1 as i64);
+            let moves: Vec<(usize, usize)> = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                .iter()
+                .map(|(dx, dy)| (x + dx, y + dy))
+                .filter(|&(a, b)| a >= 0 && b >= 0 && a < 3 && b < 3)
+                .map(|(a, b)| (a as usize, b as usize))
+                .collect();
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            let tile = moves[(seed >> 33) as usize % moves.len()];
+            let next = grid.slide(tile);
+            h = strategy.process_delta(h, &grid, &next, homes, (blank, tile));
+            assert_eq!(
+                strategy.process(&next, goal),
+                h,
+                "{:?} {:?}",
+                strategy,
+                next
+            );
+            grid = next;
+        }
+    }
+
+    #[test]
+    fn test_incremental() {
+        let grid = &[vec![1, 2, 3], vec![8, 0, 4], vec![7, 6, 5]];
+        let db = PatternDatabase::build(grid, vec![vec![1, 2, 3, 8], vec![4, 5, 6, 7]]);
+        let table = PerfectTable::build(grid).unwrap();
+        let goal = &Board::new(grid).unwrap();
+        let heuristiques = vec![
+            Heuristique::Hamming,
+            Heuristique::Manhattan,
+            Heuristique::LinearConflict,
+            Heuristique::WalkingDistance(None),
+            Heuristique::PatternDatabase(Partition::Default, Some(Arc::new(db))),
+            Heuristique::Perfect(Some(Arc::new(table))),
+        ];
+        for heuristique in heuristiques {
+            check_incremental(Strategy::Standard(heuristique.clone()), goal);
+            if !heuristique.whole_grid() {
+                check_incremental(Strategy::Sandwich(vec![], heuristique.clone()), goal);
+                check_incremental(Strategy::Cross(vec![], heuristique), goal);
+            }
+        }
+    }
+
     #[test]
     fn test_resolve_1() {
-        let initial = &vec![vec![1, 2, 3], vec![4, 5, 6], vec![8, 7, 0]];
-        let goal = &vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 0]];
+        let initial = Board::new(&[vec![1, 2, 3], vec![4, 5, 6], vec![8, 7, 0]]).unwrap();
+        let goal = Board::new(&[vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 0]]).unwrap();
         assert_eq!(
             2f64,
-            Strategy::Standard(Heuristique::Manhattan).process(initial, goal)
+            Strategy::Standard(Heuristique::Manhattan).process(&initial, &goal)
         );
     }
 }
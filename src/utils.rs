@@ -1,24 +1,27 @@
 extern crate rand;
 
-use rand::Rng;
+use super::board::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
+/*
+ * Tiles placed before each goal tile in the initial order, the blank aside
+ */
 fn interversion(map: &[Vec<i64>], goal: &[Vec<i64>]) -> usize {
     let mut initial: Vec<&i64> = map.iter().flatten().filter(|&&x| x != 0).collect();
-    let mut goal: Vec<&i64> = goal.iter().flatten().filter(|&&x| x != 0).collect();
     let mut res: usize = 0;
-    while !goal.is_empty() {
-        let g = goal.remove(0);
-        let pos: usize = initial.iter().position(|&x| x == g).unwrap() as usize;
-        res += initial[0..pos].iter().count() as usize;
+    for g in goal.iter().flatten().filter(|&&x| x != 0) {
+        let pos = initial.iter().position(|&x| x == g).unwrap();
+        res += pos;
         initial.remove(pos);
     }
     res
 }
 
 pub fn find_nb<T: std::cmp::PartialEq>(nb: T, map: &[Vec<T>]) -> (i32, i32) {
-    for x in 0..map.len() {
-        for y in 0..map.len() {
-            if nb == map[x][y] {
+    for (x, row) in map.iter().enumerate() {
+        for (y, cell) in row.iter().enumerate() {
+            if nb == *cell {
                 return (x as i32, y as i32);
             }
         }
@@ -26,185 +29,327 @@ pub fn find_nb<T: std::cmp::PartialEq>(nb: T, map: &[Vec<T>]) -> (i32, i32) {
     (-1, -1)
 }
 
-pub fn solvable(initial: &[Vec<i64>], goal: &[Vec<i64>]) -> bool {
-    if initial == goal {
-        return true;
-    }
-    let interv = interversion(initial, goal);
-    let size = goal.len();
-    let res = match size % 2 {
-        1 => match interv % 2 {
-            0 => true,
-            _ => false,
-        },
-        0 => {
-            let (x, _) = find_nb(0, initial);
-            (x % 2 == 0 && interv % 2 == 1) || (x % 2 == 1 && interv % 2 == 0)
-        }
-        _ => false,
-    };
-    res
+/*
+ * Why a puzzle can or cannot reach its goal
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solvability {
+    /* Inversions between the tile orders of the initial and goal grids */
+    pub inversions: usize,
+    /* Fewest swaps of two cells (blank included) turning initial into goal */
+    pub transpositions: usize,
+    /* Taxicab distance between the initial and goal blank positions */
+    pub blank_distance: usize,
+    pub solvable: bool,
 }
 
-pub fn creat_new_rand(size: usize) -> String {
-    let mut new_grid = Vec::new();
-    let mut rng = rand::thread_rng();
-    new_grid.push(0 as i64);
-    for i in 1..size.pow(2) {
-        new_grid.insert(rng.gen_range(0, i + 1), i as i64);
-    }
-    let mut return_value = format!("{}\n", size);
-    for _i in 0..size {
-        let temp_vect: String = new_grid
-            .drain(..size)
-            .map(|x| x.to_string())
-            .collect::<Vec<String>>()
-            .join(" ");
-        return_value = format!("{}\n{}", return_value, temp_vect);
-    }
-    return_value
+impl fmt::Display for Solvability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parity = |n: usize| if n % 2 == 1 { "odd" } else { "even" };
+        write!(
+            f,
+            "permutation parity {} ({} transpositions), blank distance {} ({}), {} inversions",
+            parity(self.transpositions),
+            self.transpositions,
+            parity(self.blank_distance),
+            self.blank_distance,
+            self.inversions
+        )
+    }
 }
 
-pub fn with_duplicate(map: &Vec<Vec<i64>>) -> bool {
-    let size = map.len();
-    for i in 0..size {
-        for j in i..size {
-            for x in 0..size {
-                for y in x..size {
-                    if map[i as usize][x as usize] == map[j as usize][y as usize]
-                        && (i != j || x != y)
-                    {
-                        println!(
-                            "{} | {}",
-                            map[i as usize][x as usize], map[j as usize][y as usize]
-                        );
-                        return true;
-                    }
-                }
+/*
+ * Every move swaps the blank with a neighbour: it flips the parity of the
+ * permutation between the grid and the goal, and the parity of the blank's
+ * distance to its goal cell. The goal is reachable iff both parities match,
+ * except on a single line where tiles can never pass each other.
+ */
+pub fn solvability(initial: &[Vec<i64>], goal: &[Vec<i64>]) -> Solvability {
+    let inversions = interversion(initial, goal);
+    let goal_cells: HashMap<i64, usize> = goal
+        .iter()
+        .flatten()
+        .enumerate()
+        .map(|(c, &t)| (t, c))
+        .collect();
+    let target: Vec<usize> = initial.iter().flatten().map(|t| goal_cells[t]).collect();
+    let mut seen = vec![false; target.len()];
+    let mut cycles = 0;
+    for c in 0..target.len() {
+        if !seen[c] {
+            cycles += 1;
+            let mut cur = c;
+            while !seen[cur] {
+                seen[cur] = true;
+                cur = target[cur];
             }
         }
     }
-    return false;
+    let transpositions = target.len() - cycles;
+    let (x, y) = find_nb(0, initial);
+    let (gx, gy) = find_nb(0, goal);
+    let blank_distance = ((x - gx).abs() + (y - gy).abs()) as usize;
+    let solvable = if goal.len() == 1 || goal[0].len() == 1 {
+        inversions == 0
+    } else {
+        transpositions % 2 == blank_distance % 2
+    };
+    Solvability {
+        inversions,
+        transpositions,
+        blank_distance,
+        solvable,
+    }
+}
+
+pub fn solvable(initial: &[Vec<i64>], goal: &[Vec<i64>]) -> bool {
+    solvability(initial, goal).solvable
+}
+
+/*
+ * Zobrist key of a tile standing on a cell, derived with splitmix64
+ * rather than drawn into a table so that any tile label can be hashed
+ */
+pub fn zobrist_key(cell: usize, tile: i64) -> u64 {
+    let mut z = ((cell as u64) << 32 ^ tile as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+pub fn zobrist(board: &Board) -> u64 {
+    board
+        .tiles()
+        .enumerate()
+        .fold(0, |hash, (cell, tile)| hash ^ zobrist_key(cell, tile))
+}
+
+pub fn with_duplicate(map: &[Vec<i64>]) -> bool {
+    let mut seen = HashSet::new();
+    !map.iter().flatten().all(|t| seen.insert(t))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::VecDeque;
     #[test]
     fn test_interversion_1() {
         let i = interversion(
-            &vec![vec![1, 2, 3], vec![4, 5, 6], vec![8, 7, 0]],
-            &vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 0]],
+            &[vec![1, 2, 3], vec![4, 5, 6], vec![8, 7, 0]],
+            &[vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 0]],
         );
-        assert_eq!(1 as usize, i);
+        assert_eq!(1, i);
     }
     #[test]
     fn test_interversion_2() {
         let i = interversion(
-            &vec![vec![0, 2, 3], vec![4, 5, 6], vec![7, 8, 1]],
-            &vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 0]],
+            &[vec![0, 2, 3], vec![4, 5, 6], vec![7, 8, 1]],
+            &[vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 0]],
         );
-        assert_eq!(7 as usize, i);
+        assert_eq!(7, i);
     }
     #[test]
     fn test_find_nb_1() {
-        let v = &vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 0]];
+        let v = &[vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 0]];
         let coord = find_nb(0, v);
         assert_eq!((2, 2), coord);
     }
     #[test]
     fn test_solvable_1() {
-        let initial = &vec![vec![1, 8, 2], vec![0, 4, 3], vec![7, 6, 5]];
-        let goal = &vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 0]];
-        assert_eq!(true, solvable(initial, goal));
+        let initial = &[vec![1, 8, 2], vec![0, 4, 3], vec![7, 6, 5]];
+        let goal = &[vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 0]];
+        assert!(solvable(initial, goal));
     }
     #[test]
     fn test_solvable_2() {
-        let initial = &vec![
+        let initial = &[
             vec![13, 2, 10, 3],
             vec![1, 12, 8, 4],
             vec![5, 0, 9, 6],
             vec![15, 14, 11, 7],
         ];
-        let goal = &vec![
+        let goal = &[
             vec![1, 2, 3, 4],
             vec![5, 6, 7, 8],
             vec![9, 10, 11, 12],
             vec![13, 14, 15, 0],
         ];
-        assert_eq!(true, solvable(initial, goal));
+        assert!(solvable(initial, goal));
     }
     #[test]
     fn test_solvable_3() {
-        let initial = &vec![
+        let initial = &[
             vec![6, 13, 7, 10],
             vec![8, 9, 11, 0],
             vec![15, 2, 12, 5],
             vec![14, 3, 1, 4],
         ];
-        let goal = &vec![
+        let goal = &[
             vec![1, 2, 3, 4],
             vec![5, 6, 7, 8],
             vec![9, 10, 11, 12],
             vec![13, 14, 15, 0],
         ];
-        assert_eq!(true, solvable(initial, goal));
+        assert!(solvable(initial, goal));
     }
     #[test]
     fn test_solvable_4() {
-        let initial = &vec![
+        let initial = &[
             vec![3, 9, 1, 15],
             vec![14, 11, 4, 6],
             vec![13, 0, 10, 12],
             vec![2, 7, 8, 5],
         ];
-        let goal = &vec![
+        let goal = &[
             vec![1, 2, 3, 4],
             vec![5, 6, 7, 8],
             vec![9, 10, 11, 12],
             vec![13, 14, 15, 0],
         ];
-        assert_eq!(false, solvable(initial, goal));
+        assert!(!solvable(initial, goal));
+    }
+    #[test]
+    fn test_solvable_rectangle() {
+        let goal = &[vec![1, 2, 3, 4], vec![8, 7, 6, 5], vec![0, 9, 10, 11]];
+        assert!(solvable(
+            &[vec![1, 2, 3, 4], vec![0, 7, 6, 5], vec![8, 9, 10, 11]],
+            goal
+        ));
+        assert!(!solvable(
+            &[vec![1, 2, 3, 4], vec![7, 8, 6, 5], vec![0, 9, 10, 11]],
+            goal
+        ));
+        let goal = &[vec![1, 2, 3], vec![4, 5, 0]];
+        assert!(solvable(&[vec![1, 2, 0], vec![4, 5, 3]], goal));
+        assert!(!solvable(&[vec![2, 1, 3], vec![4, 5, 0]], goal));
+    }
+    /*
+     * Every grid reachable from the goal, by BFS
+     */
+    fn reachable(goal: &[Vec<i64>]) -> HashSet<Vec<Vec<i64>>> {
+        let (rows, cols) = (goal.len() as i32, goal[0].len() as i32);
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        seen.insert(goal.to_vec());
+        queue.push_back(goal.to_vec());
+        while let Some(grid) = queue.pop_front() {
+            let (x, y) = find_nb(0, &grid);
+            for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)].iter() {
+                let (a, b) = (x + dx, y + dy);
+                if a < 0 || b < 0 || a >= rows || b >= cols {
+                    continue;
+                }
+                let mut next = grid.clone();
+                next[x as usize][y as usize] = next[a as usize][b as usize];
+                next[a as usize][b as usize] = 0;
+                if seen.insert(next.clone()) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        seen
+    }
+
+    /*
+     * Every arrangement of the goal tiles, in the goal's shape
+     */
+    fn arrangements(goal: &[Vec<i64>]) -> Vec<Vec<Vec<i64>>> {
+        let cols = goal[0].len();
+        let mut tiles: Vec<i64> = goal.iter().flatten().cloned().collect();
+        let mut res = vec![];
+        permute(&mut tiles, 0, &mut |t| {
+            res.push(t.chunks(cols).map(|row| row.to_vec()).collect())
+        });
+        res
+    }
+
+    fn permute(tiles: &mut Vec<i64>, k: usize, f: &mut dyn FnMut(&[i64])) {
+        if k == tiles.len() {
+            return f(tiles);
+        }
+        for i in k..tiles.len() {
+            tiles.swap(k, i);
+            permute(tiles, k + 1, f);
+            tiles.swap(k, i);
+        }
+    }
+
+    #[test]
+    fn test_solvability_exhaustive() {
+        let goals = [
+            vec![vec![1, 2], vec![3, 0]],
+            vec![vec![1, 0], vec![3, 2]],
+            vec![vec![1, 2, 3], vec![0, 5, 4]],
+            vec![vec![1, 2, 3], vec![4, 5, 0]],
+            vec![vec![1, 2, 0]],
+            vec![vec![1, 2, 3], vec![8, 0, 4], vec![7, 6, 5]],
+        ];
+        for goal in goals.iter() {
+            let reachable = reachable(goal);
+            for grid in arrangements(goal) {
+                assert_eq!(
+                    reachable.contains(&grid),
+                    solvable(&grid, goal),
+                    "{:?} -> {:?}",
+                    grid,
+                    goal
+                );
+            }
+        }
+    }
+    #[test]
+    fn test_solvability_explanation() {
+        let goal = &[vec![1, 2, 3], vec![8, 0, 4], vec![7, 6, 5]];
+        let res = solvability(&[vec![1, 2, 3], vec![8, 4, 0], vec![7, 6, 5]], goal);
+        assert_eq!(
+            (1, 1, true),
+            (res.transpositions, res.blank_distance, res.solvable)
+        );
+        let res = solvability(&[vec![2, 1, 3], vec![8, 0, 4], vec![7, 6, 5]], goal);
+        assert_eq!(
+            (1, 0, false),
+            (res.transpositions, res.blank_distance, res.solvable)
+        );
     }
     #[test]
     fn test_duplicate_1() {
-        let initial = &vec![
+        let initial = &[
             vec![1, 9, 1, 15],
             vec![14, 11, 4, 6],
             vec![13, 0, 10, 12],
             vec![2, 7, 8, 5],
         ];
-        assert_eq!(true, with_duplicate(initial));
+        assert!(with_duplicate(initial));
     }
     #[test]
     fn test_duplicate_2() {
-        let initial = &vec![
+        let initial = &[
             vec![1, 2, 3, 4],
             vec![5, 6, 7, 8],
             vec![9, 10, 11, 12],
             vec![13, 14, 15, 0],
         ];
-        assert_eq!(false, with_duplicate(initial));
+        assert!(!with_duplicate(initial));
     }
     #[test]
     fn test_duplicate_3() {
-        let initial = &vec![
+        let initial = &[
             vec![1, 2, 3, 4],
             vec![5, 1, 7, 8],
             vec![9, 10, 11, 12],
             vec![13, 14, 15, 0],
         ];
-        assert_eq!(true, with_duplicate(initial));
+        assert!(with_duplicate(initial));
     }
     #[test]
     fn test_duplicate_4() {
-        let initial = &vec![
+        let initial = &[
             vec![1, 2, 3, 4],
             vec![5, 6, 7, 8],
             vec![9, 10, 11, 12],
             vec![1, 14, 15, 0],
         ];
-        assert_eq!(true, with_duplicate(initial));
+        assert!(with_duplicate(initial));
     }
-
 }
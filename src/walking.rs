@@ -0,0 +1,266 @@
+use super::board::*;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+/*
+ * Walking distance of one axis: a state counts, for every line (row or
+ * column), how many tiles of each goal line it holds, plus the line of
+ * the blank. Only moves along the axis change it.
+ */
+struct LineTable {
+    lines: usize,
+    distances: HashMap<Vec<u8>, u8>,
+}
+
+impl LineTable {
+    /*
+     * BFS from the goal state, where every line holds its own tiles
+     */
+    fn build(lines: usize, width: usize, blank: usize) -> LineTable {
+        let mut start = vec![0u8; lines * lines + 1];
+        for l in 0..lines {
+            start[l * lines + l] = (if l == blank { width - 1 } else { width }) as u8;
+        }
+        start[lines * lines] = blank as u8;
+
+        let mut distances = HashMap::new();
+        let mut queue = VecDeque::new();
+        distances.insert(start.clone(), 0u8);
+        queue.push_back(start);
+        while let Some(state) = queue.pop_front() {
+            let distance = distances[&state];
+            let b = state[lines * lines] as usize;
+            let around = [b.wrapping_sub(1), b + 1];
+            for &from in around.iter().filter(|&&l| l < lines) {
+                for k in 0..lines {
+                    if state[from * lines + k] == 0 {
+                        continue;
+                    }
+                    let mut next = state.clone();
+                    next[from * lines + k] -= 1;
+                    next[b * lines + k] += 1;
+                    next[lines * lines] = from as u8;
+                    if !distances.contains_key(&next) {
+                        distances.insert(next.clone(), distance + 1);
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        LineTable { lines, distances }
+    }
+
+    /*
+     * tiles yields (current line, goal line) for every tile but the blank
+     */
+    fn state<I: Iterator<Item = (usize, usize)>>(&self, tiles: I, blank: usize) -> Vec<u8> {
+        let mut state = vec![0u8; self.lines * self.lines + 1];
+        for (line, home) in tiles {
+            state[line * self.lines + home] += 1;
+        }
+        state[self.lines * self.lines] = blank as u8;
+        state
+    }
+
+    fn distance(&self, state: &[u8]) -> f64 {
+        self.distances.get(state).map_or(0f64, |&d| d as f64)
+    }
+}
+
+/*
+ * Inversions of a tile sequence read in the order of their goal ranks
+ */
+fn inversions(ranks: &[usize]) -> usize {
+    let mut res = 0;
+    for (i, a) in ranks.iter().enumerate() {
+        res += ranks[i + 1..].iter().filter(|&b| b < a).count();
+    }
+    res
+}
+
+/*
+ * Fewest moves along an axis to undo the inversions: each one jumps a tile
+ * over step other tiles, changing the count by at most step and by the
+ * same parity as step
+ */
+fn inversion_distance(inversions: usize, step: usize) -> usize {
+    if step == 0 {
+        return 0;
+    }
+    let moves = inversions.div_ceil(step);
+    if step % 2 == 1 && moves % 2 != inversions % 2 {
+        moves + 1
+    } else {
+        moves
+    }
+}
+
+/*
+ * Walking distance heuristique: the row and column tables are combined
+ * with the inversion distance of each axis, keeping the larger one
+ */
+pub struct WalkingDistance {
+    rows: usize,
+    cols: usize,
+    home: Homes,
+    by_rows: LineTable,
+    by_columns: LineTable,
+}
+
+impl fmt::Debug for WalkingDistance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "WalkingDistance({} + {} states)",
+            self.by_rows.distances.len(),
+            self.by_columns.distances.len()
+        )
+    }
+}
+
+impl WalkingDistance {
+    pub fn new(goal: &Board) -> WalkingDistance {
+        let (rows, cols) = (goal.rows(), goal.cols());
+        let (x, y) = goal.blank();
+        WalkingDistance {
+            rows,
+            cols,
+            by_rows: LineTable::build(rows, cols, x),
+            by_columns: LineTable::build(cols, rows, y),
+            home: Homes::new(goal),
+        }
+    }
+
+    /*
+     * A cell seen along an axis: its line (row for vertical moves, column
+     * for horizontal ones) and its rank in the order the axis keeps
+     * (row-major for vertical moves, column-major for horizontal ones)
+     */
+    fn along(&self, vertical: bool, (i, j): (usize, usize)) -> (usize, usize) {
+        if vertical {
+            (i, i * self.cols + j)
+        } else {
+            (j, j * self.rows + i)
+        }
+    }
+
+    /*
+     * Line state and inversions of the grid along an axis
+     */
+    fn measure(&self, grid: &Board, vertical: bool) -> (Vec<u8>, usize) {
+        let mut tiles: Vec<((usize, usize), (usize, usize))> = (0..grid.len())
+            .map(|c| ((c / self.cols, c % self.cols), grid.get(c)))
+            .filter(|&(_, t)| t != 0)
+            .map(|(cell, t)| {
+                (
+                    self.along(vertical, cell),
+                    self.along(vertical, self.home.get(t).unwrap()),
+                )
+            })
+            .collect();
+        let state = self.table(vertical).state(
+            tiles.iter().map(|&((line, _), (home, _))| (line, home)),
+            self.along(vertical, grid.blank()).0,
+        );
+        tiles.sort_by_key(|&((_, rank), _)| rank);
+        let ranks: Vec<usize> = tiles.iter().map(|&(_, (_, rank))| rank).collect();
+        (state, inversions(&ranks))
+    }
+
+    fn table(&self, vertical: bool) -> &LineTable {
+        if vertical {
+            &self.by_rows
+        } else {
+            &self.by_columns
+        }
+    }
+
+    /*
+     * Walking distance of an axis, or its inversion distance when larger:
+     * a vertical move jumps a tile over the rest of its row, and
+     * conversely for a horizontal one
+     */
+    fn axis(&self, vertical: bool, (state, inversions): &(Vec<u8>, usize)) -> f64 {
+        let step = if vertical { self.cols } else { self.rows } - 1;
+        let id = inversion_distance(*inversions, step) as f64;
+        self.table(vertical).distance(state).max(id)
+    }
+
+    pub fn value(&self, grid: &Board) -> f64 {
+        self.axis(true, &self.measure(grid, true)) + self.axis(false, &self.measure(grid, false))
+    }
+
+    /*
+     * Change of the value when the tile at `tile` slides into the empty
+     * space at `blank`: the move only changes the axis it is made along,
+     * the tile leaving its line for the blank's and jumping over the tiles
+     * between them in the order of that axis
+     */
+    pub fn value_delta(&self, parent: &Board, blank: (usize, usize), tile: (usize, usize)) -> f64 {
+        let vertical = blank.0 != tile.0;
+        let before = self.measure(parent, vertical);
+
+        let (from, a) = self.along(vertical, tile);
+        let (to, b) = self.along(vertical, blank);
+        let (home, rank) = self.along(vertical, self.home.get(parent.at(tile)).unwrap());
+        let lines = self.table(vertical).lines;
+        let mut state = before.0.clone();
+        state[from * lines + home] -= 1;
+        state[to * lines + home] += 1;
+        state[lines * lines] = from as u8;
+        let cell = |k: usize| {
+            if vertical {
+                (k / self.cols, k % self.cols)
+            } else {
+                (k % self.rows, k / self.rows)
+            }
+        };
+        let mut inversions = before.1 as i64;
+        for k in a.min(b) + 1..a.max(b) {
+            let (_, jumped) = self.along(vertical, self.home.get(parent.at(cell(k))).unwrap());
+            inversions += if (rank < jumped) == (a < b) { 1 } else { -1 };
+        }
+        let after = (state, inversions as usize);
+        self.axis(vertical, &after) - self.axis(vertical, &before)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_walking_distance() {
+        let board = |grid: &[Vec<i64>]| Board::new(grid).unwrap();
+        let goal = &board(&[vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 0]]);
+        let wd = WalkingDistance::new(goal);
+        assert_eq!(0f64, wd.value(goal));
+        assert_eq!(
+            1f64,
+            wd.value(&board(&[vec![1, 2, 3], vec![4, 5, 6], vec![7, 0, 8]]))
+        );
+        // Six moves of the blank, each one bringing a tile home
+        assert_eq!(
+            6f64,
+            wd.value(&board(&[vec![4, 1, 3], vec![7, 2, 6], vec![0, 5, 8]]))
+        );
+        // One of the two 31 moves configurations: 12 vertical, 13 horizontal
+        assert_eq!(
+            25f64,
+            wd.value(&board(&[vec![8, 6, 7], vec![2, 5, 4], vec![3, 0, 1]]))
+        );
+        // 8 3 4 / 2 6 5 / 1 0 7 is 13 moves away from the snail goal
+        let snail = &board(&[vec![1, 2, 3], vec![8, 0, 4], vec![7, 6, 5]]);
+        let wd = WalkingDistance::new(snail);
+        assert_eq!(0f64, wd.value(snail));
+        assert_eq!(
+            11f64,
+            wd.value(&board(&[vec![8, 3, 4], vec![2, 6, 5], vec![1, 0, 7]]))
+        );
+    }
+    #[test]
+    fn test_inversion_distance() {
+        assert_eq!(2, inversion_distance(2, 3));
+        assert_eq!(3, inversion_distance(5, 3));
+        assert_eq!(2, inversion_distance(4, 2));
+    }
+}